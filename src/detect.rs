@@ -0,0 +1,88 @@
+use crate::source::LineSource;
+use crate::time::{Parser, YearHint, Zone};
+use crate::{modified, path_of, Preset, Problem, PRESETS};
+use chrono::NaiveDateTime;
+use regex::bytes::Regex;
+use std::time::SystemTime;
+
+// the number of lines sampled from each of the head, middle, and tail of a log
+const SAMPLE_SIZE: usize = 20;
+
+/// Chooses the preset whose format best fits a log.
+///
+/// Lines are sampled from the head, middle, and tail of the log. The preset
+/// chosen is the one that finds the most timestamps in the sample in
+/// increasing order: each timestamp no earlier than the one before it counts
+/// for the preset and each earlier one against it. Ties go to the preset that
+/// comes first in [`PRESETS`].
+///
+/// # Errors
+/// [`Problem::NoTimestamps`] if no preset finds a timestamp in the sample, or
+/// [`Problem::Io`] if the log cannot be read.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::{detect_format, Source};
+///
+/// let mut source = Source::from(&b"Jan 12 03:00:01 myhost sshd[1234]: ok\n"[..]);
+/// assert_eq!("syslog", detect_format(&mut source).unwrap().name);
+/// ```
+pub fn detect_format<S: LineSource>(source: &mut S) -> Result<&'static Preset, Problem> {
+    let lines = sample(source)?;
+    // timestamps without a year are placed before the log was last modified
+    let year = YearHint::Before(Zone::utc().at(modified(source).unwrap_or_else(SystemTime::now)));
+    let mut best: Option<(&Preset, isize)> = None;
+    for preset in PRESETS {
+        let parser = Parser {
+            formats: vec![Regex::new(preset.format).unwrap()],
+            log_zone: None,
+            query_zone: Zone::utc(),
+            year,
+        };
+        if let Some(score) = score(&parser, &lines) {
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((preset, score));
+            }
+        }
+    }
+    match best {
+        Some((preset, _)) => Ok(preset),
+        None => Err(Problem::NoTimestamps {
+            path: path_of(source),
+        }),
+    }
+}
+
+// the lines at the head, middle, and tail of the source, in order
+fn sample<S: LineSource>(source: &mut S) -> Result<Vec<Vec<u8>>, Problem> {
+    let len = source.len();
+    let middle = len.saturating_sub(SAMPLE_SIZE) / 2;
+    let tail = len.saturating_sub(SAMPLE_SIZE);
+    let mut indices: Vec<usize> = (0..SAMPLE_SIZE)
+        .chain(middle..middle + SAMPLE_SIZE)
+        .chain(tail..tail + SAMPLE_SIZE)
+        .filter(|&i| i < len)
+        .collect();
+    indices.sort_unstable();
+    indices.dedup();
+    indices
+        .into_iter()
+        .map(|i| source.line(i).map_err(|e| Problem::io(source, e)))
+        .collect()
+}
+
+// the number of lines with a timestamp no earlier than the one before it less
+// the number with one that is earlier; None if there are no timestamps at all
+fn score(parser: &Parser, lines: &[Vec<u8>]) -> Option<isize> {
+    let mut previous: Option<NaiveDateTime> = None;
+    let mut score = 0;
+    for t in lines.iter().filter_map(|line| parser.timestamp(line)) {
+        if previous.is_none_or(|p| p <= t) {
+            score += 1;
+        } else {
+            score -= 1;
+        }
+        previous = Some(t);
+    }
+    previous.map(|_| score)
+}
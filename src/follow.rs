@@ -0,0 +1,248 @@
+use crate::source::LineSource;
+use crate::time::Parser;
+use crate::{Lines, Problem};
+use chrono::NaiveDateTime;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// how long to wait before looking for more of the log
+const POLL: Duration = Duration::from_millis(200);
+// the number of bytes to read from the log at once
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// A blocking iterator over the lines found by
+/// [`Search::follow`](crate::Search::follow).
+///
+/// Once the lines already in the log have been yielded, the log is watched for
+/// more, as by `tail -f`, until a line is timestamped at or after the end of
+/// the range. If the log is rotated by renaming it and creating another in its
+/// place, the rest of the old log is read and then the new one is followed
+/// from its beginning. If the log is truncated, it is followed from its
+/// beginning.
+///
+/// A line is only yielded once it is complete, so the last line of a log still
+/// being written is held back until its line terminator is written.
+pub struct Follow {
+    lines: Option<Lines<Box<dyn LineSource>>>,
+    path: PathBuf,
+    // the log as it is followed and its identity, once following begins
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    // how far into the log has been read, and the bytes not yet yielded
+    position: u64,
+    pending: Vec<u8>,
+    // the index of the next line in the log
+    next: usize,
+    ranges: Vec<(usize, usize)>,
+    // whether the next line found begins a range, as in a new log
+    new_range: bool,
+    misordered: Vec<(usize, usize)>,
+    parser: Parser,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    // no line at or after this time can be in the range
+    stop_time: NaiveDateTime,
+    // whether the line last timestamped is in the range
+    keep: bool,
+    done: bool,
+}
+
+impl Follow {
+    // follow a log from the lines found in it, or from line `next` at byte
+    // `position` if none were
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        lines: Option<Lines<Box<dyn LineSource>>>,
+        path: &Path,
+        next: usize,
+        position: u64,
+        parser: Parser,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        stop_time: NaiveDateTime,
+    ) -> Follow {
+        Follow {
+            lines,
+            path: path.to_path_buf(),
+            file: None,
+            identity: None,
+            position,
+            pending: vec![],
+            next,
+            ranges: vec![],
+            new_range: true,
+            misordered: vec![],
+            parser,
+            start,
+            end,
+            stop_time,
+            keep: false,
+            done: false,
+        }
+    }
+    /// The indices of the first and last lines found in each log followed: the
+    /// log searched and then, if it was rotated, each log that took its place.
+    pub fn ranges(&self) -> &[(usize, usize)] {
+        &self.ranges
+    }
+    /// The indices of the first and last lines of each region where
+    /// timestamps were found out of order while looking for the start of the
+    /// range, as by [`Lines::misordered`].
+    pub fn misordered(&self) -> &[(usize, usize)] {
+        match &self.lines {
+            Some(lines) => lines.misordered(),
+            None => &self.misordered,
+        }
+    }
+    /// The path of the log followed.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    // note that line i of the current log was found
+    fn found(&mut self, i: usize) {
+        match self.ranges.last_mut() {
+            Some((_, last)) if !self.new_range => *last = i,
+            _ => self.ranges.push((i, i)),
+        }
+        self.new_range = false;
+    }
+    // the next complete line written to the log, waiting for it as needed
+    fn next_line(&mut self) -> io::Result<Vec<u8>> {
+        loop {
+            if let Some(n) = line_length(&self.pending) {
+                return Ok(self.pending.drain(..n).collect());
+            }
+            let file = match self.file.as_mut() {
+                Some(file) => file,
+                None => {
+                    let mut file = File::open(&self.path)?;
+                    self.identity = identity(&file.metadata()?);
+                    file.seek(SeekFrom::Start(self.position))?;
+                    self.file.get_or_insert(file)
+                }
+            };
+            let mut buf = vec![0; CHUNK_SIZE];
+            let n = file.read(&mut buf)?;
+            if n > 0 {
+                self.pending.extend_from_slice(&buf[..n]);
+                self.position += n as u64;
+                continue;
+            }
+            // at the end of the log, which may have been rotated or truncated
+            let replaced = match fs::metadata(&self.path) {
+                Ok(metadata) if identity(&metadata) != self.identity => true,
+                Ok(metadata) => metadata.len() < self.position,
+                // between the log's rotation and its replacement's creation
+                Err(_) => false,
+            };
+            if replaced {
+                // the last line of the old log will not be finished
+                if !self.pending.is_empty() {
+                    return Ok(mem::take(&mut self.pending));
+                }
+                self.file = None;
+                self.position = 0;
+                self.next = 0;
+                self.new_range = true;
+                continue;
+            }
+            thread::sleep(POLL);
+        }
+    }
+}
+
+impl Iterator for Follow {
+    type Item = Result<Vec<u8>, Problem>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(lines) = self.lines.as_mut() {
+            match lines.next() {
+                // a last line not yet finished is read again once it is
+                Some(Ok(line)) if lines.last != Some(self.next) => {
+                    let i = lines.last.unwrap();
+                    self.found(i);
+                    return Some(Ok(line));
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                _ => {}
+            }
+            let lines = self.lines.take().unwrap();
+            if lines.done {
+                // the range ended within the lines found
+                self.done = true;
+                return None;
+            }
+            self.keep = lines.keep;
+            self.misordered = lines.misordered;
+        }
+        loop {
+            let line = match self.next_line().map_err(Problem::io_at(&self.path)) {
+                Ok(line) => line,
+                Err(problem) => {
+                    self.done = true;
+                    return Some(Err(problem));
+                }
+            };
+            let i = self.next;
+            self.next += 1;
+            if let Some(t) = self.parser.timestamp(&line) {
+                if t >= self.stop_time {
+                    self.done = true;
+                    return None;
+                }
+                self.keep = t >= self.start && t < self.end;
+            }
+            if self.keep {
+                self.found(i);
+                return Some(Ok(line));
+            }
+        }
+    }
+}
+
+// the length of the first complete line in the bytes, if there is one; a
+// carriage return at the end may yet be followed by a newline
+fn line_length(bytes: &[u8]) -> Option<usize> {
+    let i = bytes.iter().position(|&b| b == b'\n' || b == b'\r')?;
+    match bytes.get(i + 1) {
+        _ if bytes[i] == b'\n' => Some(i + 1),
+        Some(b'\n') => Some(i + 2),
+        Some(_) => Some(i + 1),
+        None => None,
+    }
+}
+
+// the index and offset of the line from which to follow a source: the end,
+// unless its last line is not yet finished
+pub(crate) fn tail_start<S: LineSource>(source: &mut S) -> io::Result<(usize, u64)> {
+    let n = source.len();
+    if n > 0 {
+        let line = source.line(n - 1)?;
+        if !(line.ends_with(b"\n") || line.ends_with(b"\r")) {
+            return Ok((n - 1, source.offset(n - 1)));
+        }
+    }
+    Ok((n, source.offset(n)))
+}
+
+// what distinguishes a file from one that replaces it at the same path
+#[cfg(unix)]
+fn identity(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// without inode numbers, a replaced log is only noticed if it is shorter
+#[cfg(not(unix))]
+fn identity(_: &Metadata) -> Option<(u64, u64)> {
+    None
+}
@@ -0,0 +1,148 @@
+use regex::bytes::Regex;
+
+/// Compiles a strftime-style timestamp format into the regular expression a
+/// [`Search`](crate::Search) uses to find timestamps.
+///
+/// The directives understood are
+///
+/// | directive | matches |
+/// |-----------|---------|
+/// | `%Y` | a four-digit year |
+/// | `%y` | a two-digit year: 69 to 99 are 1969 to 1999, 00 to 68 are 2000 to 2068 |
+/// | `%m` | a month number |
+/// | `%b`, `%h` | an abbreviated month name such as `Jan` |
+/// | `%B` | a month name such as `January` |
+/// | `%d`, `%e` | a day of the month, possibly padded with a space |
+/// | `%H`, `%k` | an hour on a 24-hour clock |
+/// | `%I`, `%l` | an hour on a 12-hour clock |
+/// | `%p` | `AM`, `pm`, `a.m.`, and the like |
+/// | `%M` | a minute |
+/// | `%S` | a second |
+/// | `%f` | the digits of a fraction of a second |
+/// | `%.f` | an optional fraction of a second, with its `.` or `,` |
+/// | `%z`, `%:z` | an offset from UTC such as `+0200` or `-05:00` |
+/// | `%Z` | `Z`, `UTC`, `GMT`, or an offset |
+/// | `%s` | seconds since the Unix epoch |
+/// | `%a`, `%A` | a day of the week, which is ignored |
+/// | `%T` | `%H:%M:%S` |
+/// | `%R` | `%H:%M` |
+/// | `%F` | `%Y-%m-%d` |
+/// | `%D` | `%m/%d/%y` |
+/// | `%%` | `%` |
+///
+/// A run of whitespace matches any run of whitespace. Anything else matches
+/// itself.
+///
+/// # Errors
+/// A description of the problem if the format has an unknown directive, gives
+/// some part of the time more than once, or has no directives at all.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::strftime;
+///
+/// let rx = strftime("%d/%b/%Y:%T %z").unwrap();
+/// assert!(rx.is_match(b"127.0.0.1 - - [12/Jan/2019:03:00:01 +0000] \"GET /\""));
+/// assert!(strftime("%Y-%m-%d %Q").is_err());
+/// ```
+pub fn strftime(spec: &str) -> Result<Regex, String> {
+    let mut pattern = String::new();
+    let mut names: Vec<&str> = vec![];
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            pattern += r"\s+";
+            continue;
+        }
+        if c != '%' {
+            pattern += &regex::escape(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+        let mut directive = String::from("%");
+        while let Some(&c) = chars.peek() {
+            directive.push(c);
+            chars.next();
+            if c != '.' && c != ':' {
+                break;
+            }
+        }
+        // the parts of the time the directive gives and the pattern for them
+        let (parts, rx): (&[&str], &str) = match directive.as_str() {
+            "%%" => (&[], "%"),
+            "%a" | "%A" => (&[], "[A-Za-z]+"),
+            "%T" => (
+                &["hour", "minute", "second"],
+                r"(?P<hour>\d{1,2}):(?P<minute>\d\d):(?P<second>\d\d)",
+            ),
+            "%R" => (&["hour", "minute"], r"(?P<hour>\d{1,2}):(?P<minute>\d\d)"),
+            "%F" => (
+                &["year", "month", "day"],
+                r"(?P<year>\d{4})-(?P<month>\d{1,2})-(?P<day>\d{1,2})",
+            ),
+            "%D" => (
+                &["year", "month", "day"],
+                r"(?P<month>\d{1,2})/(?P<day>\d{1,2})/(?P<year>\d\d)",
+            ),
+            "%Y" => (&["year"], r"(?P<year>\d{4})"),
+            "%y" => (&["year"], r"(?P<year>\d\d)"),
+            "%m" => (&["month"], r"(?P<month>\d{1,2})"),
+            "%b" | "%h" => (&["month"], r"(?P<month>[A-Za-z]{3})"),
+            "%B" => (&["month"], r"(?P<month>[A-Za-z]+)"),
+            "%d" | "%e" => (&["day"], r"\s?(?P<day>\d{1,2})"),
+            "%H" | "%k" | "%I" | "%l" => (&["hour"], r"\s?(?P<hour>\d{1,2})"),
+            "%p" => (&["ampm"], r"(?P<ampm>[AaPp]\.?[Mm]\.?)"),
+            "%M" => (&["minute"], r"(?P<minute>\d\d)"),
+            "%S" => (&["second"], r"(?P<second>\d\d)"),
+            "%f" => (&["fraction"], r"(?P<fraction>\d+)"),
+            "%.f" => (&["fraction"], r"(?:[.,](?P<fraction>\d+))?"),
+            "%z" | "%:z" => (&["offset"], r"(?P<offset>[+-]\d\d:?\d\d)"),
+            "%Z" => (&["offset"], r"(?P<offset>[Zz]|UTC|GMT|[+-]\d\d(?::?\d\d)?)"),
+            "%s" => (&["epoch"], r"(?P<epoch>\d+)"),
+            _ => return Err(format!("unknown directive {}", directive)),
+        };
+        for &part in parts {
+            if names.contains(&part) {
+                return Err(format!("the {} is given more than once", part));
+            }
+            names.push(part);
+        }
+        pattern += rx;
+    }
+    if names.is_empty() {
+        return Err(format!(
+            "no directives for a time; a regular expression must be prefixed with \"{}\"",
+            REGEX_PREFIX
+        ));
+    }
+    Regex::new(&pattern).map_err(|e| e.to_string())
+}
+
+/// The prefix that marks a format given to [`parse_format`] as a regular expression
+/// rather than a strftime-style format.
+pub const REGEX_PREFIX: &str = "regex:";
+
+/// Compiles a timestamp format, which is either a strftime-style format as
+/// understood by [`strftime`] or, if it begins with [`REGEX_PREFIX`], a regular
+/// expression with named captures.
+///
+/// # Errors
+/// A description of the problem if the format cannot be compiled.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::parse_format;
+///
+/// let a = parse_format("%Y-%m-%d %T").unwrap();
+/// let b = parse_format(r"regex:(?P<year>\d{4})-(?P<month>\d\d)-(?P<day>\d\d)").unwrap();
+/// assert!(a.is_match(b"2019-01-12 03:00:01"));
+/// assert!(b.is_match(b"2019-01-12 03:00:01"));
+/// ```
+pub fn parse_format(spec: &str) -> Result<Regex, String> {
+    match spec.strip_prefix(REGEX_PREFIX) {
+        Some(rx) => Regex::new(rx).map_err(|e| e.to_string()),
+        None => strftime(spec),
+    }
+}
@@ -0,0 +1,534 @@
+use crate::source::{index_lines, invalid, LineSource};
+use crate::varint::{read_number, write_number};
+use crc32fast::Hasher;
+use miniz_oxide::inflate::core::inflate_flags::{
+    TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY,
+};
+use miniz_oxide::inflate::core::{decompress, BlockBoundaryState, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// the size of the history a deflate stream may refer back into
+const WINDOW_SIZE: usize = 32 * 1024;
+// the number of decompressed bytes between checkpoints
+const SPAN: u64 = 1 << 20;
+// the number of compressed bytes read at a time
+const INPUT_SIZE: usize = 1 << 16;
+// the first bytes of an index written by write_index
+const INDEX_MAGIC: &[u8] = b"hun gzip index 1\n";
+
+/// A [`LineSource`] over a gzip-compressed log.
+///
+/// Constructing a `GzipSource` decompresses the log once to find where its
+/// lines begin. Along the way it takes a checkpoint about every megabyte: the
+/// position in the compressed data of the boundary between two deflate blocks
+/// together with the 32K of text before it. Reading from any point after that
+/// means decompressing from the nearest checkpoint before it rather than from
+/// the beginning of the file.
+///
+/// Concatenated gzip members, as produced by appending to a `.gz` file, are
+/// read as one log.
+///
+/// The index of lines and checkpoints can be saved with
+/// [`write_index`](GzipSource::write_index) and reused with
+/// [`with_index`](GzipSource::with_index) to avoid decompressing the whole log
+/// again. The `hun` command does not do so, even with `--index`; saving the
+/// index is left to programs that search the same compressed log repeatedly.
+pub struct GzipSource<R> {
+    inflater: Inflater<R>,
+    offsets: Vec<u64>,
+    checkpoints: Vec<Checkpoint>,
+    path: Option<PathBuf>,
+}
+
+impl<R: Read + Seek> GzipSource<R> {
+    /// Constructs a `GzipSource`, decompressing all of `reader` to index it.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while reading the data. Data that is not
+    /// gzip-compressed, or that is corrupt or truncated, produces an error of
+    /// kind `InvalidData`.
+    pub fn new(reader: R) -> io::Result<GzipSource<R>> {
+        let mut inflater = Inflater::new(reader)?;
+        inflater.checkpoints = Some(vec![]);
+        let offsets = index_lines(&mut inflater)?;
+        let checkpoints = inflater.checkpoints.take().unwrap_or_default();
+        Ok(GzipSource {
+            inflater,
+            offsets,
+            checkpoints,
+            path: None,
+        })
+    }
+    /// Constructs a `GzipSource` from an index previously written by
+    /// [`write_index`](GzipSource::write_index) without decompressing the log.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while reading the index. An index that is
+    /// malformed or truncated or was written for compressed data of a
+    /// different length produces an error of kind `InvalidData`.
+    pub fn with_index<I: Read>(mut reader: R, mut index: I) -> io::Result<GzipSource<R>> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        // the index takes less room read whole than the offsets and
+        // checkpoints made from it, and its length bounds how many there are
+        let mut data = vec![];
+        index.read_to_end(&mut data)?;
+        let (offsets, checkpoints) = read_index(&data, length).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                invalid("truncated gzip index")
+            } else {
+                e
+            }
+        })?;
+        Ok(GzipSource {
+            inflater: Inflater::new(reader)?,
+            offsets,
+            checkpoints,
+            path: None,
+        })
+    }
+    /// Writes the index of lines and checkpoints so that it can be reused by
+    /// [`with_index`](GzipSource::with_index).
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while writing.
+    pub fn write_index<W: Write>(&mut self, mut out: W) -> io::Result<()> {
+        let length = self.inflater.reader.seek(SeekFrom::End(0))?;
+        out.write_all(INDEX_MAGIC)?;
+        write_number(&mut out, length)?;
+        write_number(&mut out, self.offsets.len() as u64)?;
+        let mut previous = 0;
+        for &offset in &self.offsets {
+            write_number(&mut out, offset - previous)?;
+            previous = offset;
+        }
+        write_number(&mut out, self.checkpoints.len() as u64)?;
+        for c in &self.checkpoints {
+            write_number(&mut out, c.input)?;
+            write_number(&mut out, c.output)?;
+            out.write_all(&[c.state.num_bits, c.state.bit_buf])?;
+            write_number(&mut out, c.window_pos as u64)?;
+            out.write_all(&c.window)?;
+        }
+        out.flush()
+    }
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inflater.reader
+    }
+}
+
+impl GzipSource<File> {
+    /// Opens and indexes the gzip-compressed file at `path`.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while opening or reading the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<GzipSource<File>> {
+        let mut source = GzipSource::new(File::open(path.as_ref())?)?;
+        source.path = Some(path.as_ref().to_path_buf());
+        Ok(source)
+    }
+}
+
+impl<R: Read + Seek> LineSource for GzipSource<R> {
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+    fn offset(&self, i: usize) -> u64 {
+        self.offsets[i]
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let here = self.inflater.position();
+        let i = self.checkpoints.partition_point(|c| c.output <= offset);
+        if i > 0 {
+            let checkpoint = &self.checkpoints[i - 1];
+            // decompressing onward from where we are is cheaper unless there
+            // is a checkpoint in the way
+            if offset < here || here < checkpoint.output {
+                self.inflater.restore(checkpoint)?;
+            }
+        }
+        let here = self.inflater.position();
+        if offset < here {
+            return Err(invalid("no gzip checkpoint before the offset"));
+        }
+        self.inflater.skip(offset - here)?;
+        self.inflater.read(buf)
+    }
+    fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+// a place from which decompression can resume
+struct Checkpoint {
+    // the offset in the compressed data at which to resume
+    input: u64,
+    // the offset in the decompressed data reached there
+    output: u64,
+    // the bits of the last byte before input that belong to the next block
+    state: BlockBoundaryState,
+    // the history the next block may refer to
+    window: Box<[u8]>,
+    window_pos: usize,
+}
+
+// where the inflater is in the gzip data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Header,
+    Deflate,
+    Trailer,
+    End,
+}
+
+// decompresses gzip members one after the other
+struct Inflater<R> {
+    reader: R,
+    // compressed bytes read but not yet consumed begin at input[consumed]
+    input: Vec<u8>,
+    consumed: usize,
+    // the offset in the compressed data of input[0]
+    input_offset: u64,
+    eof: bool,
+    decompressor: Box<DecompressorOxide>,
+    // a ring buffer of decompressed bytes, which is also the deflate history
+    window: Box<[u8]>,
+    window_pos: usize,
+    // decompressed bytes in the window not yet returned by read
+    pending: std::ops::Range<usize>,
+    // the number of bytes decompressed so far, including pending ones
+    produced: u64,
+    stage: Stage,
+    members: usize,
+    // checkpoints taken, if they are being collected
+    checkpoints: Option<Vec<Checkpoint>>,
+    // the checksum of the current member so far, if it is being checked
+    crc: Option<Hasher>,
+    // the offset in the decompressed data at which the current member begins
+    member_start: u64,
+}
+
+impl<R: Read + Seek> Inflater<R> {
+    fn new(mut reader: R) -> io::Result<Inflater<R>> {
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(Inflater {
+            reader,
+            input: Vec::with_capacity(INPUT_SIZE),
+            consumed: 0,
+            input_offset: 0,
+            eof: false,
+            decompressor: Box::default(),
+            window: vec![0; WINDOW_SIZE].into_boxed_slice(),
+            window_pos: 0,
+            pending: 0..0,
+            produced: 0,
+            stage: Stage::Header,
+            members: 0,
+            checkpoints: None,
+            crc: None,
+            member_start: 0,
+        })
+    }
+    // the offset in the decompressed data of the next byte read
+    fn position(&self) -> u64 {
+        self.produced - self.pending.len() as u64
+    }
+    // resume decompression at the checkpoint
+    fn restore(&mut self, checkpoint: &Checkpoint) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(checkpoint.input))?;
+        self.input.clear();
+        self.consumed = 0;
+        self.input_offset = checkpoint.input;
+        self.eof = false;
+        *self.decompressor = DecompressorOxide::from_block_boundary_state(&checkpoint.state);
+        self.window.copy_from_slice(&checkpoint.window);
+        self.window_pos = checkpoint.window_pos;
+        self.pending = 0..0;
+        self.produced = checkpoint.output;
+        self.stage = Stage::Deflate;
+        // a checkpoint is never taken before the first member's header
+        self.members = self.members.max(1);
+        // only whole members can be checked
+        self.crc = None;
+        Ok(())
+    }
+    // read more compressed data, returning whether there was any
+    fn fill(&mut self) -> io::Result<bool> {
+        self.input.drain(..self.consumed);
+        self.input_offset += self.consumed as u64;
+        self.consumed = 0;
+        let start = self.input.len();
+        self.input.resize(start + INPUT_SIZE, 0);
+        let n = loop {
+            match self.reader.read(&mut self.input[start..]) {
+                Ok(n) => break n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.input.truncate(start);
+                    return Err(e);
+                }
+            }
+        };
+        self.input.truncate(start + n);
+        self.eof = n == 0;
+        Ok(n > 0)
+    }
+    // make sure at least n unconsumed compressed bytes are buffered, returning
+    // whether there are that many
+    fn ensure(&mut self, n: usize) -> io::Result<bool> {
+        while self.input.len() - self.consumed < n {
+            if !self.fill()? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    // consume a gzip member header, returning whether there was one
+    fn header(&mut self) -> io::Result<bool> {
+        if !self.ensure(10)? || self.input[self.consumed..self.consumed + 2] != [0x1f, 0x8b] {
+            if self.members == 0 {
+                return Err(invalid("not gzip data"));
+            }
+            // like gzip, ignore anything after the last member
+            return Ok(false);
+        }
+        let h = &self.input[self.consumed..self.consumed + 10];
+        if h[2] != 8 {
+            return Err(invalid("unknown gzip compression method"));
+        }
+        let flags = h[3];
+        self.consumed += 10;
+        if flags & 4 != 0 {
+            // extra field
+            if !self.ensure(2)? {
+                return Err(truncated());
+            }
+            let n = u16::from_le_bytes([self.input[self.consumed], self.input[self.consumed + 1]]);
+            self.consumed += 2;
+            if !self.ensure(n as usize)? {
+                return Err(truncated());
+            }
+            self.consumed += n as usize;
+        }
+        for flag in [8, 16] {
+            // file name and comment
+            if flags & flag != 0 {
+                loop {
+                    if !self.ensure(1)? {
+                        return Err(truncated());
+                    }
+                    self.consumed += 1;
+                    if self.input[self.consumed - 1] == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        if flags & 2 != 0 {
+            // header checksum
+            if !self.ensure(2)? {
+                return Err(truncated());
+            }
+            self.consumed += 2;
+        }
+        self.members += 1;
+        *self.decompressor = DecompressorOxide::new();
+        self.member_start = self.produced;
+        if self.checkpoints.is_some() {
+            // check members while indexing, when they are read whole
+            self.crc = Some(Hasher::new());
+        }
+        self.checkpoint(BlockBoundaryState::default());
+        Ok(true)
+    }
+    // take a checkpoint here, if checkpoints are being collected and it has
+    // been long enough since the last
+    fn checkpoint(&mut self, state: BlockBoundaryState) {
+        let produced = self.produced;
+        if let Some(checkpoints) = &mut self.checkpoints {
+            if checkpoints
+                .last()
+                .is_none_or(|c| c.output + SPAN <= produced)
+            {
+                checkpoints.push(Checkpoint {
+                    input: self.input_offset + self.consumed as u64,
+                    output: produced,
+                    state,
+                    window: self.window.clone(),
+                    window_pos: self.window_pos,
+                });
+            }
+        }
+    }
+    // decompress what can be decompressed without more input or room
+    fn inflate(&mut self) -> io::Result<()> {
+        if self.consumed == self.input.len() && !self.eof {
+            self.fill()?;
+        }
+        let mut flags = 0;
+        if !self.eof {
+            flags |= TINFL_FLAG_HAS_MORE_INPUT;
+        }
+        if self.checkpoints.is_some() {
+            flags |= TINFL_FLAG_STOP_ON_BLOCK_BOUNDARY;
+        }
+        let (status, n_in, n_out) = decompress(
+            &mut self.decompressor,
+            &self.input[self.consumed..],
+            &mut self.window,
+            self.window_pos,
+            flags,
+        );
+        self.consumed += n_in;
+        self.pending = self.window_pos..self.window_pos + n_out;
+        self.window_pos = (self.window_pos + n_out) % WINDOW_SIZE;
+        self.produced += n_out as u64;
+        if let Some(crc) = &mut self.crc {
+            crc.update(&self.window[self.pending.clone()]);
+        }
+        match status {
+            TINFLStatus::Done => self.stage = Stage::Trailer,
+            TINFLStatus::HasMoreOutput => (),
+            TINFLStatus::NeedsMoreInput => {
+                if self.eof || !self.fill()? {
+                    return Err(truncated());
+                }
+            }
+            TINFLStatus::BlockBoundary => {
+                if let Some(state) = self.decompressor.block_boundary_state() {
+                    self.checkpoint(state);
+                }
+            }
+            _ => return Err(invalid("corrupt gzip data")),
+        }
+        Ok(())
+    }
+    // discard the next n decompressed bytes
+    fn skip(&mut self, mut n: u64) -> io::Result<()> {
+        let mut buf = [0; 4096];
+        while n > 0 {
+            if !self.pending.is_empty() {
+                let m = (self.pending.len() as u64).min(n) as usize;
+                self.pending.start += m;
+                n -= m as u64;
+            } else {
+                let m = self.read(&mut buf[..(n.min(4096) as usize)])?;
+                if m == 0 {
+                    return Ok(());
+                }
+                n -= m as u64;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for Inflater<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if !self.pending.is_empty() {
+                let n = self.pending.len().min(buf.len());
+                buf[..n].copy_from_slice(&self.window[self.pending.start..self.pending.start + n]);
+                self.pending.start += n;
+                return Ok(n);
+            }
+            match self.stage {
+                Stage::End => return Ok(0),
+                Stage::Header => {
+                    self.stage = if self.header()? {
+                        Stage::Deflate
+                    } else {
+                        Stage::End
+                    }
+                }
+                Stage::Deflate => self.inflate()?,
+                Stage::Trailer => {
+                    // the checksum and length of the member
+                    if !self.ensure(8)? {
+                        return Err(truncated());
+                    }
+                    let trailer = &self.input[self.consumed..self.consumed + 8];
+                    if let Some(crc) = self.crc.take() {
+                        let length = (self.produced - self.member_start) as u32;
+                        if trailer[..4] != crc.finalize().to_le_bytes()
+                            || trailer[4..] != length.to_le_bytes()
+                        {
+                            return Err(invalid("gzip checksum mismatch"));
+                        }
+                    }
+                    self.consumed += 8;
+                    self.stage = Stage::Header;
+                }
+            }
+        }
+    }
+}
+
+// the offsets of lines and the checkpoints in an index written by write_index
+// for compressed data of the given length
+fn read_index(mut index: &[u8], length: u64) -> io::Result<(Vec<u64>, Vec<Checkpoint>)> {
+    let malformed = || invalid("malformed gzip index");
+    if !index.starts_with(INDEX_MAGIC) {
+        return Err(invalid("not a gzip index"));
+    }
+    index = &index[INDEX_MAGIC.len()..];
+    if read_number(&mut index)? != length {
+        return Err(invalid("the gzip index is for a different file"));
+    }
+    // each offset takes at least a byte of the index
+    let count = read_number(&mut index)?;
+    if count > index.len() as u64 {
+        return Err(malformed());
+    }
+    let mut offsets = Vec::with_capacity(count as usize);
+    let mut offset = 0u64;
+    for _ in 0..count {
+        offset = offset
+            .checked_add(read_number(&mut index)?)
+            .ok_or_else(malformed)?;
+        offsets.push(offset);
+    }
+    // and each checkpoint a window's worth
+    let count = read_number(&mut index)?;
+    if count > (index.len() / WINDOW_SIZE) as u64 {
+        return Err(malformed());
+    }
+    let mut checkpoints = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let input = read_number(&mut index)?;
+        let output = read_number(&mut index)?;
+        let mut bits = [0; 2];
+        index.read_exact(&mut bits)?;
+        let window_pos = read_number(&mut index)? as usize;
+        let mut window = vec![0; WINDOW_SIZE].into_boxed_slice();
+        index.read_exact(&mut window)?;
+        if bits[0] > 7 || window_pos >= WINDOW_SIZE || input > length {
+            return Err(malformed());
+        }
+        checkpoints.push(Checkpoint {
+            input,
+            output,
+            state: BlockBoundaryState {
+                num_bits: bits[0],
+                bit_buf: bits[1],
+                ..BlockBoundaryState::default()
+            },
+            window,
+            window_pos,
+        });
+    }
+    if offsets.is_empty() || checkpoints.first().is_none_or(|c| c.output != 0) {
+        return Err(malformed());
+    }
+    Ok((offsets, checkpoints))
+}
+
+fn truncated() -> io::Error {
+    invalid("truncated gzip data")
+}
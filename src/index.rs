@@ -0,0 +1,414 @@
+use crate::source::{invalid, is_compressed, read_exact_at, scan_lines, LineSource};
+use crate::time::Parser;
+use crate::varint::{read_number, write_number};
+use chrono::{DateTime, NaiveDateTime};
+use regex::bytes::Regex;
+use std::cell::RefCell;
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+// the number of bytes of log between samples
+const SPAN: u64 = 1 << 20;
+// the most bytes of a sampled line read to find its timestamp
+const HEAD_SIZE: u64 = 1 << 12;
+// the number of spans whose line offsets are kept at once
+const CACHED_SPANS: usize = 2;
+// the first bytes of an index written by write_index
+const INDEX_MAGIC: &[u8] = b"hun index 1\n";
+// what is appended to the name of a log to name its index
+pub(crate) const INDEX_SUFFIX: &str = ".hunidx";
+
+/// A [`LineSource`] over an uncompressed log with a sparse index kept in a
+/// file beside it, such as `app.log.hunidx` for `app.log`.
+///
+/// A [`Source`](crate::Source) reads the whole log to find where each of its
+/// lines begins, which for a log of many gigabytes takes a while every time it
+/// is searched. The index instead records a sample about every megabyte: the
+/// number and position of the first line in that span of the log and, once
+/// the log has been searched with [`Search::iter_indexed`](crate::Search::iter_indexed),
+/// the timestamp on that line. Where the other lines begin is only worked out
+/// a span at a time as they are needed.
+///
+/// Opening an `IndexedSource` creates the index if there is none. An index
+/// is used as it is if the log's size and modification time are those
+/// recorded in it. If the log has only grown since, the index is brought up to
+/// date by reading what was added. Otherwise the log is indexed afresh. If the
+/// index cannot be written, as in a directory the searcher cannot write to,
+/// the log is still searched, but it is indexed afresh each time.
+pub struct IndexedSource {
+    file: File,
+    path: PathBuf,
+    length: u64,
+    lines: usize,
+    // the log's modification time when it was indexed, in seconds and
+    // nanoseconds since the epoch
+    modified: (u64, u32),
+    samples: Vec<Sample>,
+    // the timestamps on the first lines of the samples and a description of
+    // the parser that found them
+    times: Vec<Option<NaiveDateTime>>,
+    times_for: String,
+    // the offsets of the lines in the spans last read, most recent last
+    spans: RefCell<Vec<(usize, Vec<u64>)>>,
+    // a failure to read a span, to be reported by the next read
+    error: RefCell<Option<io::Error>>,
+}
+
+// the first line of a span of the log
+#[derive(Clone, Copy)]
+struct Sample {
+    line: usize,
+    offset: u64,
+}
+
+impl IndexedSource {
+    /// Opens the uncompressed log at `path`, creating or updating its index as
+    /// needed.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while opening or reading the log. A
+    /// compressed log produces an error of kind `InvalidData`; use
+    /// [`open_indexed`](crate::open_indexed) to open logs that may be
+    /// compressed.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<IndexedSource> {
+        let path = path.as_ref();
+        if is_compressed(path)? {
+            return Err(invalid("a compressed log cannot be indexed"));
+        }
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let mut source = IndexedSource {
+            file,
+            path: path.to_path_buf(),
+            length: 0,
+            lines: 0,
+            modified: modified(&metadata),
+            samples: vec![],
+            times: vec![],
+            times_for: String::new(),
+            spans: RefCell::new(vec![]),
+            error: RefCell::new(None),
+        };
+        let index = File::open(IndexedSource::index_path(path))
+            .and_then(|index| source.read_index(BufReader::new(index)));
+        match index {
+            Ok((modified, _)) if modified == source.modified && metadata.len() == source.length => {
+                return Ok(source)
+            }
+            Ok((_, checksum))
+                if metadata.len() >= source.length && source.checksum()? == checksum =>
+            {
+                // the log has grown, so the last span may have too
+                source.times.truncate(source.samples.len() - 1);
+            }
+            _ => {
+                source.samples = vec![Sample { line: 0, offset: 0 }];
+                source.times.clear();
+            }
+        }
+        source.modified = modified(&metadata);
+        source.scan()?;
+        source.save();
+        Ok(source)
+    }
+    /// Returns the path of the index kept for the log at `path`.
+    pub fn index_path<P: AsRef<Path>>(path: P) -> PathBuf {
+        let mut name = path.as_ref().as_os_str().to_os_string();
+        name.push(INDEX_SUFFIX);
+        PathBuf::from(name)
+    }
+    // find the timestamps on the first lines of any samples without them,
+    // starting over if they were found by a different parser
+    pub(crate) fn sample_times(&mut self, parser: &Parser) -> io::Result<()> {
+        let described = describe(parser);
+        if self.times_for != described {
+            self.times.clear();
+            self.times_for = described;
+        }
+        if self.times.len() == self.samples.len() {
+            return Ok(());
+        }
+        for k in self.times.len()..self.samples.len() {
+            let head = self.head(k)?;
+            self.times.push(parser.timestamp(&head));
+        }
+        self.save();
+        Ok(())
+    }
+    // the lines of the last sample timestamped before t and the first
+    // timestamped at or after it, according to the times found by
+    // sample_times
+    pub(crate) fn bracket(&self, t: NaiveDateTime) -> (Option<usize>, Option<usize>) {
+        let after = self
+            .times
+            .iter()
+            .position(|u| matches!(u, Some(u) if *u >= t));
+        let before = self.times[..after.unwrap_or(self.times.len())]
+            .iter()
+            .rposition(|u| matches!(u, Some(u) if *u < t));
+        let line = |k: usize| self.samples[k].line;
+        (before.map(line), after.map(line))
+    }
+    // index the lines from the last sample on, taking another sample at the
+    // first line to begin a span or more after the one before
+    fn scan(&mut self) -> io::Result<()> {
+        let last = *self.samples.last().unwrap();
+        self.file.seek(SeekFrom::Start(last.offset))?;
+        let mut line = last.line;
+        let mut next = last.offset + SPAN;
+        let samples = &mut self.samples;
+        let read = scan_lines(&mut self.file, |offset| {
+            let offset = last.offset + offset;
+            if offset >= next {
+                samples.push(Sample { line, offset });
+                next = offset + SPAN;
+            }
+            line += 1;
+        })?;
+        self.lines = line;
+        self.length = last.offset + read;
+        self.spans.get_mut().clear();
+        Ok(())
+    }
+    // the bounds of span k in bytes and in lines
+    fn span(&self, k: usize) -> ((u64, u64), (usize, usize)) {
+        let sample = self.samples[k];
+        match self.samples.get(k + 1) {
+            Some(next) => ((sample.offset, next.offset), (sample.line, next.line)),
+            None => ((sample.offset, self.length), (sample.line, self.lines)),
+        }
+    }
+    // the offsets of the lines in span k
+    fn offsets(&self, k: usize) -> io::Result<Vec<u64>> {
+        let ((start, end), (first, last)) = self.span(k);
+        let mut bytes = vec![0; (end - start) as usize];
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut bytes)?;
+        let mut offsets = Vec::with_capacity(last - first);
+        scan_lines(&mut &bytes[..], |offset| offsets.push(start + offset))?;
+        if offsets.len() != last - first {
+            return Err(invalid("the log has changed since it was indexed"));
+        }
+        Ok(offsets)
+    }
+    // the beginning of the first line of span k, which is enough to find its
+    // timestamp
+    fn head(&mut self, k: usize) -> io::Result<Vec<u8>> {
+        let ((start, end), _) = self.span(k);
+        let mut buf = vec![0; (end - start).min(HEAD_SIZE) as usize];
+        read_exact_at(self, start, &mut buf)?;
+        if let Some(i) = buf.iter().position(|&b| b == b'\n' || b == b'\r') {
+            buf.truncate(i + 1);
+        }
+        Ok(buf)
+    }
+    // the checksum of the last span of the log as indexed
+    fn checksum(&mut self) -> io::Result<u32> {
+        let ((start, end), _) = self.span(self.samples.len() - 1);
+        let mut buf = vec![0; (end - start) as usize];
+        read_exact_at(self, start, &mut buf)?;
+        Ok(crc32fast::hash(&buf))
+    }
+    // write the index, ignoring failure since it only saves time
+    fn save(&mut self) {
+        self.write_index().ok();
+    }
+    // write the index beside the log, replacing any index already there
+    fn write_index(&mut self) -> io::Result<()> {
+        let checksum = self.checksum()?;
+        let path = IndexedSource::index_path(&self.path);
+        let dir = match path.parent() {
+            Some(dir) if dir != Path::new("") => dir,
+            _ => Path::new("."),
+        };
+        // other searches may be reading the index, so it is replaced whole
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        // whoever can read the log can use its index
+        temp.as_file()
+            .set_permissions(self.file.metadata()?.permissions())?;
+        {
+            let mut out = BufWriter::new(temp.as_file_mut());
+            out.write_all(INDEX_MAGIC)?;
+            write_number(&mut out, self.length)?;
+            write_number(&mut out, self.modified.0)?;
+            write_number(&mut out, self.modified.1 as u64)?;
+            write_number(&mut out, checksum as u64)?;
+            write_number(&mut out, self.lines as u64)?;
+            write_number(&mut out, self.samples.len() as u64)?;
+            let mut previous = Sample { line: 0, offset: 0 };
+            for &sample in &self.samples {
+                write_number(&mut out, (sample.line - previous.line) as u64)?;
+                write_number(&mut out, sample.offset - previous.offset)?;
+                previous = sample;
+            }
+            write_number(&mut out, self.times_for.len() as u64)?;
+            out.write_all(self.times_for.as_bytes())?;
+            write_number(&mut out, self.times.len() as u64)?;
+            for t in &self.times {
+                match t {
+                    Some(t) => {
+                        let t = t.and_utc();
+                        out.write_all(&[1])?;
+                        // zigzag encoding keeps times before the epoch short
+                        let seconds = t.timestamp();
+                        write_number(&mut out, ((seconds << 1) ^ (seconds >> 63)) as u64)?;
+                        write_number(&mut out, t.timestamp_subsec_nanos() as u64)?;
+                    }
+                    None => out.write_all(&[0])?,
+                }
+            }
+            out.flush()?;
+        }
+        temp.persist(&path).map_err(|e| e.error)?;
+        Ok(())
+    }
+    // read an index written by write_index, returning the modification time
+    // and the checksum of the last span it records
+    fn read_index<I: Read>(&mut self, mut index: I) -> io::Result<((u64, u32), u32)> {
+        let mut magic = vec![0; INDEX_MAGIC.len()];
+        index.read_exact(&mut magic)?;
+        if magic != INDEX_MAGIC {
+            return Err(invalid("not an index"));
+        }
+        self.length = read_number(&mut index)?;
+        let modified = (read_number(&mut index)?, read_number(&mut index)? as u32);
+        let checksum = read_number(&mut index)? as u32;
+        self.lines = read_number(&mut index)? as usize;
+        let mut previous = Sample { line: 0, offset: 0 };
+        for k in 0..read_number(&mut index)? {
+            let line = previous.line + read_number(&mut index)? as usize;
+            let offset = previous.offset + read_number(&mut index)?;
+            let out_of_order = if k == 0 {
+                offset != 0 || line != 0
+            } else {
+                offset <= previous.offset || line <= previous.line
+            };
+            if out_of_order || offset > self.length || line > self.lines {
+                return Err(invalid("malformed index"));
+            }
+            previous = Sample { line, offset };
+            self.samples.push(previous);
+        }
+        let n = read_number(&mut index)?;
+        if self.samples.is_empty() || n > 1 << 16 {
+            return Err(invalid("malformed index"));
+        }
+        let mut described = vec![0; n as usize];
+        index.read_exact(&mut described)?;
+        self.times_for = String::from_utf8(described).map_err(|_| invalid("malformed index"))?;
+        let n = read_number(&mut index)?;
+        if n > self.samples.len() as u64 {
+            return Err(invalid("malformed index"));
+        }
+        for _ in 0..n {
+            let mut flag = [0];
+            index.read_exact(&mut flag)?;
+            let t = match flag[0] {
+                0 => None,
+                1 => {
+                    let n = read_number(&mut index)?;
+                    let seconds = (n >> 1) as i64 ^ -((n & 1) as i64);
+                    let nanos = read_number(&mut index)? as u32;
+                    let t = DateTime::from_timestamp(seconds, nanos)
+                        .ok_or_else(|| invalid("malformed index"))?;
+                    Some(t.naive_utc())
+                }
+                _ => return Err(invalid("malformed index")),
+            };
+            self.times.push(t);
+        }
+        Ok((modified, checksum))
+    }
+}
+
+impl LineSource for IndexedSource {
+    fn len(&self) -> usize {
+        self.lines
+    }
+    fn offset(&self, i: usize) -> u64 {
+        if i >= self.lines {
+            return self.length;
+        }
+        let k = self.samples.partition_point(|s| s.line <= i) - 1;
+        let sample = self.samples[k];
+        if sample.line == i {
+            return sample.offset;
+        }
+        let mut spans = self.spans.borrow_mut();
+        match spans.iter().position(|&(j, _)| j == k) {
+            Some(j) => {
+                let span = spans.remove(j);
+                spans.push(span);
+            }
+            None => match self.offsets(k) {
+                Ok(offsets) => {
+                    if spans.len() == CACHED_SPANS {
+                        spans.remove(0);
+                    }
+                    spans.push((k, offsets));
+                }
+                Err(e) => {
+                    // the error is reported by the next read
+                    *self.error.borrow_mut() = Some(e);
+                    return sample.offset;
+                }
+            },
+        }
+        spans.last().unwrap().1[i - sample.line]
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(e) = self.error.get_mut().take() {
+            return Err(e);
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read(buf)
+    }
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+    fn line(&mut self, i: usize) -> io::Result<Vec<u8>> {
+        if i >= self.lines {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("index {} in source of only {} lines", i, self.lines),
+            ));
+        }
+        let start = self.offset(i);
+        let end = self.offset(i + 1);
+        if let Some(e) = self.error.get_mut().take() {
+            return Err(e);
+        }
+        let mut buf = vec![0; (end - start) as usize];
+        read_exact_at(self, start, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+// a description of what a parser finds timestamps with, so that times found
+// by a different parser are not trusted; the year of a timestamp without one
+// depends on the hint it was placed with
+fn describe(parser: &Parser) -> String {
+    let formats: Vec<&str> = parser.formats.iter().map(Regex::as_str).collect();
+    let dated = |rx: &Regex| {
+        rx.capture_names()
+            .flatten()
+            .any(|name| name == "year" || name.starts_with("epoch"))
+    };
+    let year = Some(parser.year).filter(|_| !parser.formats.iter().all(dated));
+    format!(
+        "{:?} {:?} {:?} {:?}",
+        formats, parser.log_zone, parser.query_zone, year
+    )
+}
+
+// when a file was last modified, in seconds and nanoseconds since the epoch
+fn modified(metadata: &Metadata) -> (u64, u32) {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or((0, 0), |d| (d.as_secs(), d.subsec_nanos()))
+}
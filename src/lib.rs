@@ -1,137 +1,1193 @@
-use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
 extern crate larry;
 use larry::Larry;
+mod detect;
+pub use detect::detect_format;
+mod follow;
+pub use follow::Follow;
+mod format;
+pub use format::{parse_format, strftime, REGEX_PREFIX};
+mod gzip;
+pub use gzip::GzipSource;
+mod index;
+pub use index::IndexedSource;
+mod periods;
+pub use periods::Periods;
+mod seekable;
+pub use seekable::ZstdSource;
+mod source;
+pub use source::{logs_in, open_indexed, open_log, rotated_logs, LineSource, Source};
+mod time;
+pub use time::Zone;
+use time::{Parser, YearHint};
+mod varint;
 extern crate chrono;
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Duration, NaiveDateTime};
 extern crate regex;
-use regex::Regex;
+use regex::bytes::Regex;
 
-// need to make this public so it can be seen in main.rs
-#[doc(hidden)]
-pub const DEFAULT_FORMAT : &'static str = r#"(?:[^\d'"`>]|^)(?P<year>[0-9]{4})\D{1,2}(?P<month>[0-9]{1,2})\D{1,2}(?P<day>[0-9]{1,2})\D{1,2}(?P<hour>[0-9]{1,2})\D{1,2}(?P<minute>[0-9]{1,2})\D{1,2}(?P<second>[0-9]{1,2})(?:[^\d'"`<]|$)"#;
+/// The timestamp format a [`Search`] uses unless it is given another.
+///
+/// This matches most `year-month-day hour:minute:second` timestamps, with an
+/// optional fraction of a second such as `.123` or `,123456` and an optional
+/// offset from UTC such as `Z` or `+02:00` directly after the seconds, while
+/// avoiding timestamps quoted in logged SQL, JSON, or XML.
+pub const DEFAULT_FORMAT: &str = r#"(?:[^\d'"`>]|^)(?P<year>[0-9]{4})\D{1,2}(?P<month>[0-9]{1,2})\D{1,2}(?P<day>[0-9]{1,2})\D{1,2}(?P<hour>[0-9]{1,2})\D{1,2}(?P<minute>[0-9]{1,2})\D{1,2}(?P<second>[0-9]{1,2})(?:[.,](?P<fraction>[0-9]+))?(?P<offset>Z|[+-][0-9]{2}(?::?[0-9]{2})?)?(?:[^\d'"`<]|$)"#;
 
-// need to make this public so it can be seen in main.rs
-#[doc(hidden)]
+/// A named timestamp format for a common kind of log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    /// The name by which the preset is chosen, such as `syslog`.
+    pub name: &'static str,
+    /// A short description of the logs the preset is for.
+    pub description: &'static str,
+    /// The regular expression that finds the timestamps.
+    pub format: &'static str,
+    /// A line such as the preset is meant to find timestamps in.
+    pub sample: &'static str,
+}
+
+/// The timestamp format presets, in alphabetical order.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "apache",
+        description: "Apache httpd or nginx access log, common or combined format",
+        format: r"\[(?P<day>\d\d)/(?P<month>[A-Za-z]{3})/(?P<year>\d{4}):(?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d) (?P<offset>[+-]\d{4})\]",
+        sample: r#"127.0.0.1 - frank [12/Jan/2019:03:00:01 +0000] "GET /apache_pb.gif HTTP/1.0" 200 2326"#,
+    },
+    Preset {
+        name: "apache-error",
+        description: "Apache httpd error log",
+        format: r"^\[[A-Za-z]{3} (?P<month>[A-Za-z]{3}) (?P<day>\d\d) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)(?:\.(?P<fraction>\d+))? (?P<year>\d{4})\]",
+        sample: "[Sat Jan 12 03:00:01.123456 2019] [core:error] [pid 1234] [client 127.0.0.1:5678] AH00126: Invalid URI in request",
+    },
+    Preset {
+        name: "default",
+        description: "the format used when no other is given",
+        format: DEFAULT_FORMAT,
+        sample: "2019-01-12 03:00:01 [INFO] service started",
+    },
+    Preset {
+        name: "go",
+        description: "Go's log package with the standard flags, with or without microseconds",
+        format: r"^(?P<year>\d{4})/(?P<month>\d\d)/(?P<day>\d\d) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)(?:\.(?P<fraction>\d+))?",
+        sample: "2019/01/12 03:00:01.123456 listening on :8080",
+    },
+    Preset {
+        name: "iso8601",
+        description: "ISO 8601 timestamps with an optional fraction and offset",
+        format: r"(?P<year>\d{4})-(?P<month>\d\d)-(?P<day>\d\d)[T ](?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)(?:[.,](?P<fraction>\d+))?(?P<offset>Z|[+-]\d\d(?::?\d\d)?)?",
+        sample: "2019-01-12T03:00:01.123Z service started",
+    },
+    Preset {
+        name: "log4j",
+        description: "log4j and logback with the ISO8601 date pattern",
+        format: r"^(?P<year>\d{4})-(?P<month>\d\d)-(?P<day>\d\d) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d),(?P<fraction>\d{3})",
+        sample: "2019-01-12 03:00:01,123 INFO  [main] com.example.App - started",
+    },
+    Preset {
+        name: "python",
+        description: "Python's logging module with %(asctime)s",
+        format: r"(?P<year>\d{4})-(?P<month>\d\d)-(?P<day>\d\d) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d),(?P<fraction>\d{3})",
+        sample: "2019-01-12 03:00:01,123 - myapp - WARNING - disk nearly full",
+    },
+    Preset {
+        name: "syslog",
+        description: "traditional BSD syslog, which has no year",
+        format: r"^(?P<month>[A-Za-z]{3}) +(?P<day>\d{1,2}) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)",
+        sample: "Jan 12 03:00:01 myhost sshd[1234]: Accepted publickey for root",
+    },
+];
+
+/// Other names for presets, each with the name of the preset it stands for.
+///
+/// nginx writes its access log with the same timestamps as Apache httpd, so
+/// `nginx` is another name for the `apache` preset.
+pub const PRESET_ALIASES: &[(&str, &str)] = &[("nginx", "apache")];
+
+/// The most buckets into which [`Search::histogram`] will divide a range.
+pub const MAX_BUCKETS: usize = 100_000;
+
+/// Returns the preset with the given name or alias, ignoring case.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::preset;
+///
+/// assert_eq!("apache", preset("NGINX").unwrap().name);
+/// assert!(preset("eniac").is_none());
+/// ```
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    let name = PRESET_ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(name))
+        .map_or(name, |&(_, name)| name);
+    PRESETS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+/// A problem encountered while searching a log.
+///
+/// Every variant carries the path of the log, if the log has one.
 #[derive(Debug)]
 pub enum Problem {
-    LogAfter,
-    LogBefore,
-    NoTimestamps,
-    MisorderedTimestamps(usize, NaiveDateTime, String, usize, NaiveDateTime, String),
+    /// Every event in the log is after the period sought.
+    LogAfter { path: Option<PathBuf> },
+    /// Every event in the log is before the period sought.
+    LogBefore { path: Option<PathBuf> },
+    /// No line of the log has a timestamp the format can parse.
+    NoTimestamps { path: Option<PathBuf> },
+    /// The timestamp on the first line given is later than that on the second.
+    /// The lines' indices, timestamps, and text are provided.
+    MisorderedTimestamps {
+        path: Option<PathBuf>,
+        first: (usize, NaiveDateTime, String),
+        second: (usize, NaiveDateTime, String),
+    },
+    /// The line bounds given to the search cannot be satisfied by the log.
+    BadLineBounds {
+        path: Option<PathBuf>,
+        start: Option<usize>,
+        end: Option<usize>,
+        lines: usize,
+    },
+    /// A histogram of the range would have more than [`MAX_BUCKETS`] buckets.
+    TooManyBuckets,
+    /// Reading the log failed.
+    Io {
+        path: Option<PathBuf>,
+        error: io::Error,
+    },
+    #[doc(hidden)]
     NormallyUnreachable, // to mark code that should only be reachable in testing
 }
 
-// need to make this public so it can be seen in main.rs
+impl Problem {
+    /// The path of the log in which the problem was encountered, if it has one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Problem::LogAfter { path }
+            | Problem::LogBefore { path }
+            | Problem::NoTimestamps { path }
+            | Problem::MisorderedTimestamps { path, .. }
+            | Problem::BadLineBounds { path, .. }
+            | Problem::Io { path, .. } => path.as_deref(),
+            Problem::TooManyBuckets | Problem::NormallyUnreachable => None,
+        }
+    }
+    // convert an error from a source into a problem
+    fn io<S: LineSource>(source: &S, error: io::Error) -> Problem {
+        Problem::Io {
+            path: path_of(source),
+            error,
+        }
+    }
+    // a conversion of errors from the log at a path into problems
+    pub(crate) fn io_at(path: &Path) -> impl Fn(io::Error) -> Problem + '_ {
+        move |error| Problem::Io {
+            path: Some(path.to_path_buf()),
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = self.path() {
+            write!(f, "{}: ", path.display())?;
+        }
+        match self {
+            Problem::LogAfter { .. } => write!(f, "events in log are after period sought"),
+            Problem::LogBefore { .. } => write!(f, "events in log are before period sought"),
+            Problem::NoTimestamps { .. } => write!(f, "no timestamps found"),
+            Problem::MisorderedTimestamps {
+                first: (i1, t1, l1),
+                second: (i2, t2, l2),
+                ..
+            } => write!(
+                f,
+                "the timestamp on line {}, {}, is misordered relative to that on line {}, {}\nline {}: {}line {}: {}",
+                i1, t1, i2, t2, i1, l1, i2, l2
+            ),
+            Problem::BadLineBounds {
+                start, end, lines, ..
+            } => match (start, end) {
+                (Some(0), _) | (_, Some(0)) => write!(f, "line numbers must be greater than 0"),
+                (Some(s), Some(e)) if s > e => {
+                    write!(f, "start line {} is greater than end line {}", s, e)
+                }
+                (Some(s), _) => write!(f, "start line {} is beyond the last line, {}", s, lines),
+                _ => write!(f, "bad line bounds"),
+            },
+            Problem::TooManyBuckets => write!(
+                f,
+                "a histogram of the period would have more than {} buckets",
+                MAX_BUCKETS
+            ),
+            Problem::Io { error, .. } => write!(f, "{}", error),
+            Problem::NormallyUnreachable => write!(f, "this should be unreachable"),
+        }
+    }
+}
+
+impl Error for Problem {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Problem::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// A query for the lines of a log falling within a period of time.
+///
+/// A `Search` is built up from a time range and then refined with optional
+/// settings before it is run against a log.
+///
+/// # Examples
+/// ```no_run
+/// # extern crate chrono;
+/// # extern crate trufflehunter;
+/// # use chrono::NaiveDate;
+/// use std::io::{self, Write};
+/// use trufflehunter::{Search, Source};
+///
+/// let start = NaiveDate::from_ymd_opt(2019, 1, 12).unwrap().and_hms_opt(3, 0, 0).unwrap();
+/// let end = NaiveDate::from_ymd_opt(2019, 1, 12).unwrap().and_hms_opt(3, 15, 0).unwrap();
+/// let source = Source::open("production.log").unwrap();
+/// let stdout = io::stdout();
+/// let mut out = stdout.lock();
+/// for line in Search::new(start, end).start_line(1000).iter(source).unwrap() {
+///     out.write_all(&line.unwrap()).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Search {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    start_line: Option<usize>,
+    end_line: Option<usize>,
+    formats: Vec<Regex>,
+    log_zone: Option<Zone>,
+    query_zone: Option<Zone>,
+    tolerance: Duration,
+}
+
+impl Search {
+    /// Constructs a search for lines timestamped at or after `start` and
+    /// before `end`, using [`DEFAULT_FORMAT`] to find timestamps.
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime) -> Search {
+        Search {
+            start,
+            end,
+            start_line: None,
+            end_line: None,
+            formats: vec![default_format()],
+            log_zone: None,
+            query_zone: None,
+            tolerance: Duration::zero(),
+        }
+    }
+    /// Sets the period sought, keeping the other settings, so that one search
+    /// may be reused for several periods. See [`Periods`].
+    pub fn period(mut self, start: NaiveDateTime, end: NaiveDateTime) -> Search {
+        self.start = start;
+        self.end = end;
+        self
+    }
+    /// Sets the first line, counting from 1, to search from.
+    pub fn start_line(mut self, n: usize) -> Search {
+        self.start_line = Some(n);
+        self
+    }
+    /// Sets the last line, counting from 1, to search to.
+    pub fn end_line(mut self, n: usize) -> Search {
+        self.end_line = Some(n);
+        self
+    }
+    /// Sets the regular expression used to find timestamps.
+    pub fn format(mut self, rx: Regex) -> Search {
+        self.formats = vec![rx];
+        self
+    }
+    /// Sets several regular expressions used to find timestamps. Each line's
+    /// timestamp is found by the first of them that can parse one on it, so a
+    /// log that changes formats part way through can still be searched.
+    ///
+    /// # Panics
+    /// If there are no regular expressions.
+    pub fn formats<I: IntoIterator<Item = Regex>>(mut self, rxs: I) -> Search {
+        self.formats = rxs.into_iter().collect();
+        assert!(!self.formats.is_empty(), "a search needs a format");
+        self
+    }
+    /// Sets the time zone of timestamps that do not capture an `offset` or
+    /// `tz`. By default these are assumed to be in the query's zone.
+    pub fn log_zone(mut self, zone: Zone) -> Search {
+        self.log_zone = Some(zone);
+        self
+    }
+    /// Sets the time zone of the search's start and end times. Timestamps are
+    /// normalized into this zone before they are compared with them. By
+    /// default this is the local time zone.
+    pub fn query_zone(mut self, zone: Zone) -> Search {
+        self.query_zone = Some(zone);
+        self
+    }
+    /// Sets how far out of order timestamps may be, as they often are when
+    /// several threads write to a log. A timestamp earlier than one before it
+    /// by less than the tolerance is accepted rather than treated as a
+    /// misordering, and the search looks that much before its start time so
+    /// that it still finds every line in the range. Lines just out of the
+    /// range are left out even where they are interleaved with those in it.
+    /// By default there is no tolerance.
+    ///
+    /// # Panics
+    /// If the tolerance is negative.
+    pub fn tolerance(mut self, tolerance: Duration) -> Search {
+        assert!(
+            tolerance >= Duration::zero(),
+            "a tolerance cannot be negative"
+        );
+        self.tolerance = tolerance;
+        self
+    }
+    /// Runs the search, returning the index of the first line found and all
+    /// the lines in the range.
+    ///
+    /// This is a convenience for small ranges. The lines are returned as text
+    /// without their line terminators and with any bytes that are not UTF-8
+    /// replaced. Use [`iter`](Search::iter) to get the lines exactly as they
+    /// appear in the log.
+    pub fn run<S: LineSource>(&self, source: S) -> Result<(usize, Vec<String>), Problem> {
+        let lines = self.iter(source)?;
+        let offset = lines.offset();
+        let mut vec = vec![];
+        for line in lines {
+            vec.push(String::from_utf8_lossy(trim_terminator(&line?)).into_owned());
+        }
+        Ok((offset, vec))
+    }
+    /// Runs the search, returning an iterator over the lines in the range.
+    ///
+    /// Lines are read from the source lazily, so the range found need not fit
+    /// in memory.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the range cannot be found, or
+    /// the log cannot be read.
+    pub fn iter<S: LineSource>(&self, source: S) -> Result<Lines<S>, Problem> {
+        self.check_bounds(&source)?;
+        let parser = self.parser(&source);
+        self.iter_between(source, parser, self.start_line, self.end_line)
+    }
+    /// Runs the search, returning where the lines in the range are in the
+    /// source rather than the lines themselves.
+    ///
+    /// The end of the range is found by a second bisection rather than by
+    /// reading every line in it, so a long period costs about as much to count
+    /// as a short one. The lines counted are all those from the first in the
+    /// range up to the first timestamped at or after its end. In a log whose
+    /// timestamps are in order these are the lines [`iter`](Search::iter)
+    /// yields, but lines out of the range that are interleaved with those in
+    /// it, as with a tolerance, are counted too.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the range cannot be found, or
+    /// the log cannot be read.
+    pub fn count<S: LineSource>(&self, mut source: S) -> Result<Extent, Problem> {
+        self.check_bounds(&source)?;
+        let parser = self.parser(&source);
+        let (first, last) = (self.start_line, self.end_line);
+        self.count_probed(&mut source, &parser, first, last, &mut vec![])
+    }
+    /// Runs the search, returning the start of each bucket of a histogram of
+    /// the range and the number of lines in it. The first bucket begins at the
+    /// start of the range and each is `width` long, but for the last, which is
+    /// cut short by the end of the range.
+    ///
+    /// Where there are many lines to a bucket, the boundaries between buckets
+    /// are found by bisection as [`count`](Search::count) finds the end of the
+    /// range, and the lines in each bucket are counted as it counts them.
+    /// Otherwise the lines in the range are read and each is counted in the
+    /// bucket of its timestamp, or of the line before it if it has none.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the width would divide the
+    /// range into more than [`MAX_BUCKETS`] buckets, the range cannot be
+    /// found, or the log cannot be read.
+    ///
+    /// # Panics
+    /// If the width is not positive.
+    pub fn histogram<S: LineSource>(
+        &self,
+        mut source: S,
+        width: Duration,
+    ) -> Result<Vec<(NaiveDateTime, usize)>, Problem> {
+        self.check_bounds(&source)?;
+        let parser = self.parser(&source);
+        let (first, last) = (self.start_line, self.end_line);
+        let found = self.histogram_probed(&mut source, parser, width, first, last, &mut vec![]);
+        found.map(|(buckets, _)| buckets)
+    }
+    /// Runs the search over the log at `path` as [`iter`](Search::iter) does,
+    /// keeping a sparse index of the log in a file beside it. See
+    /// [`IndexedSource`].
+    ///
+    /// The index records the timestamps found about every megabyte of the log,
+    /// so unless line bounds are given the search begins between the two of
+    /// these on either side of the start of the range. Compressed logs are
+    /// searched without an index, as if opened by [`open_log`].
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the range cannot be found, or
+    /// the log cannot be read.
+    pub fn iter_indexed<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Lines<Box<dyn LineSource>>, Problem> {
+        let path = path.as_ref();
+        if source::is_compressed(path).map_err(Problem::io_at(path))? {
+            return self.iter(open_log(path).map_err(Problem::io_at(path))?);
+        }
+        let source = IndexedSource::open(path).map_err(Problem::io_at(path))?;
+        self.iter_index(source, path)
+    }
+    /// Runs the search over several logs taken together as one timeline, such
+    /// as a log and its rotated predecessors, returning an iterator over the
+    /// lines found in each log searched.
+    ///
+    /// The logs may be given in any order. They are put in the order of their
+    /// first timestamps, and only those whose timestamps span some part of the
+    /// period sought are searched. The lines of each log are yielded by its
+    /// own iterator, so those of logs that overlap in time are not
+    /// interleaved. Logs without any timestamps, such as a log rotated
+    /// moments ago, are passed over. Line bounds are not applied.
+    ///
+    /// # Errors
+    /// A [`Problem`] if no log has timestamps, every log is before or after
+    /// the period sought, or a log cannot be read.
+    pub fn iter_all<S, I>(&self, sources: I) -> Result<Vec<Lines<S>>, Problem>
+    where
+        S: LineSource,
+        I: IntoIterator<Item = S>,
+    {
+        let mut spans = vec![];
+        for mut source in sources {
+            if let Some((t1, t2)) = self.span(&mut source, None)? {
+                spans.push((t1, t2, source));
+            }
+        }
+        let search = self.without_line_bounds();
+        self.timeline(spans)?
+            .into_iter()
+            .map(|source| search.iter(source))
+            .collect()
+    }
+    /// Runs the search over the logs at the given paths, taken together as one
+    /// timeline as by [`iter_all`](Search::iter_all), but opening with
+    /// [`open_log`] only those logs that span some part of the period sought.
+    ///
+    /// The span of an uncompressed log is found from the lines at its head
+    /// and tail alone, so a log outside the period is passed over without
+    /// reading the rest of it.
+    ///
+    /// # Errors
+    /// A [`Problem`] if no log has timestamps, every log is before or after
+    /// the period sought, or a log cannot be read.
+    pub fn iter_paths<P, I>(&self, paths: I) -> Result<Vec<Lines<Box<dyn LineSource>>>, Problem>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = P>,
+    {
+        let (start, end) = self.widened();
+        let mut spans = vec![];
+        for path in paths {
+            let path = path.as_ref();
+            let (mut span, mut opened) = (None, None);
+            if let Some((mut ends, tail)) = source::log_ends(path).map_err(Problem::io_at(path))? {
+                span = self.span(&mut ends, Some(tail))?;
+            }
+            if span.is_none() {
+                // the log is compressed or small, or its ends have no timestamps
+                let mut source = open_log(path).map_err(Problem::io_at(path))?;
+                span = self.span(&mut source, None)?;
+                opened = Some(source);
+            }
+            if let Some((t1, t2)) = span {
+                // a log opened whole is kept to be searched rather than opened
+                // again, but only if it spans some part of the period
+                let opened = opened.filter(|_| t1 <= end && t2 >= start);
+                spans.push((t1, t2, (path.to_path_buf(), opened)));
+            }
+        }
+        let search = self.without_line_bounds();
+        self.timeline(spans)?
+            .into_iter()
+            .map(|(path, opened)| {
+                let source = match opened {
+                    Some(source) => source,
+                    None => open_log(&path).map_err(Problem::io_at(&path))?,
+                };
+                search.iter(source)
+            })
+            .collect()
+    }
+    /// Runs the search over the uncompressed log at `path` as
+    /// [`iter`](Search::iter) does, but rather than stopping at the end of the
+    /// log, waits for more lines to be written to it until one is timestamped
+    /// at or after the end of the range. See [`Follow`].
+    ///
+    /// A range that begins after the last line of the log is not a problem;
+    /// the log is simply followed from its end. Line bounds are not applied.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the log is compressed, every event in it is after the
+    /// period sought, or it cannot be read.
+    pub fn follow<P: AsRef<Path>>(&self, path: P) -> Result<Follow, Problem> {
+        let path = path.as_ref();
+        followable(path)?;
+        let mut source = Source::open(path).map_err(Problem::io_at(path))?;
+        let parser = self.parser(&source);
+        let tail = follow::tail_start(&mut source).map_err(Problem::io_at(path))?;
+        let source: Box<dyn LineSource> = Box::new(source);
+        let search = self.without_line_bounds();
+        let found = search.iter_between(source, parser.clone(), None, None);
+        self.follow_from(path, found, tail, parser)
+    }
+    /// Follows the log at `path` as [`follow`](Search::follow) does, keeping a
+    /// sparse index of the log as [`iter_indexed`](Search::iter_indexed)
+    /// does.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the log is compressed, every event in it is after the
+    /// period sought, or it cannot be read.
+    pub fn follow_indexed<P: AsRef<Path>>(&self, path: P) -> Result<Follow, Problem> {
+        let path = path.as_ref();
+        followable(path)?;
+        let mut source = IndexedSource::open(path).map_err(Problem::io_at(path))?;
+        let parser = self.parser(&source);
+        let tail = follow::tail_start(&mut source).map_err(Problem::io_at(path))?;
+        let found = self.without_line_bounds().iter_index(source, path);
+        self.follow_from(path, found, tail, parser)
+    }
+}
+
+impl Search {
+    // what turns lines into times for this search
+    fn parser<S: LineSource>(&self, source: &S) -> Parser {
+        let query_zone = self.query_zone.unwrap_or(Zone::Local);
+        // timestamps without a year are placed before the log was last
+        // modified or, failing that, near the start of the period sought
+        let year = match modified(source) {
+            Some(t) => YearHint::Before(self.log_zone.unwrap_or(query_zone).at(t)),
+            None => YearHint::Near(self.start),
+        };
+        Parser {
+            formats: self.formats.clone(),
+            log_zone: self.log_zone.filter(|&z| z != query_zone),
+            query_zone,
+            year,
+        }
+    }
+    // the first and last timestamps in a source, if it has any; for the ends
+    // of a log joined at line `tail`, None unless the first is found in the
+    // head and the last in the tail, as otherwise neither end of the log is
+    // known
+    fn span<S: LineSource>(
+        &self,
+        source: &mut S,
+        tail: Option<usize>,
+    ) -> Result<Option<(NaiveDateTime, NaiveDateTime)>, Problem> {
+        let parser = self.parser(source);
+        match get_timestamp(source, 0, &parser, true)? {
+            Some((i1, t1)) => {
+                let i = source.len() - 1;
+                let (i2, t2) = get_timestamp(source, i, &parser, false)?.unwrap();
+                match tail {
+                    Some(tail) if i1 >= tail || i2 < tail => Ok(None),
+                    _ => Ok(Some((t1, t2))),
+                }
+            }
+            None => Ok(None),
+        }
+    }
+    // the logs whose spans overlap the period sought, in order by time
+    fn timeline<T>(
+        &self,
+        mut spans: Vec<(NaiveDateTime, NaiveDateTime, T)>,
+    ) -> Result<Vec<T>, Problem> {
+        let (start, end) = self.widened();
+        if spans.is_empty() {
+            return Err(Problem::NoTimestamps { path: None });
+        }
+        if spans.iter().all(|&(t1, _, _)| t1 > end) {
+            return Err(Problem::LogAfter { path: None });
+        }
+        if spans.iter().all(|&(_, t2, _)| t2 < start) {
+            return Err(Problem::LogBefore { path: None });
+        }
+        spans.sort_by_key(|&(t1, _, _)| t1);
+        Ok(spans
+            .into_iter()
+            .filter(|&(t1, t2, _)| t1 <= end && t2 >= start)
+            .map(|(_, _, log)| log)
+            .collect())
+    }
+    // run the search over an indexed log, looking for the start of the range
+    // between the samples on either side of it
+    fn iter_index(
+        &self,
+        mut source: IndexedSource,
+        path: &Path,
+    ) -> Result<Lines<Box<dyn LineSource>>, Problem> {
+        self.check_bounds(&source)?;
+        let parser = self.parser(&source);
+        let (mut first, mut last) = (self.start_line, self.end_line);
+        if first.is_none() && last.is_none() {
+            source.sample_times(&parser).map_err(Problem::io_at(path))?;
+            let start = self.widened().0;
+            let (before, after) = source.bracket(start);
+            // the times were found when the log was sampled, so they are
+            // checked against the log as it is now
+            let mut timestamp = |i| source.line(i).map(|line| parser.timestamp(&line));
+            if let Some(i) = before {
+                if timestamp(i)
+                    .map_err(Problem::io_at(path))?
+                    .is_some_and(|t| t < start)
+                {
+                    first = Some(i + 1);
+                }
+            }
+            if let Some(i) = after {
+                if timestamp(i)
+                    .map_err(Problem::io_at(path))?
+                    .is_some_and(|t| t >= start)
+                {
+                    last = Some(i + 1);
+                }
+            }
+        }
+        self.iter_between(Box::new(source), parser, first, last)
+    }
+    // follow a log from the lines found in it or, if the range begins after
+    // them, from the line and offset given
+    fn follow_from(
+        &self,
+        path: &Path,
+        found: Result<Lines<Box<dyn LineSource>>, Problem>,
+        (next, position): (usize, u64),
+        parser: Parser,
+    ) -> Result<Follow, Problem> {
+        let lines = match found {
+            Ok(lines) => Some(lines),
+            Err(Problem::LogBefore { .. }) | Err(Problem::NoTimestamps { .. }) => None,
+            Err(e) => return Err(e),
+        };
+        Ok(Follow::new(
+            lines,
+            path,
+            next,
+            position,
+            parser,
+            self.start,
+            self.end,
+            self.widened().1,
+        ))
+    }
+    // run the search, looking for the start of the range between the given
+    // line bounds
+    fn iter_between<S: LineSource>(
+        &self,
+        source: S,
+        parser: Parser,
+        first: Option<usize>,
+        last: Option<usize>,
+    ) -> Result<Lines<S>, Problem> {
+        self.iter_probed(source, parser, first, last, &mut vec![])
+    }
+    // run the search as iter_between does, noting the lines probed while
+    // looking for the start of the range with their timestamps
+    fn iter_probed<S: LineSource>(
+        &self,
+        mut source: S,
+        parser: Parser,
+        first: Option<usize>,
+        last: Option<usize>,
+        probes: &mut Vec<(usize, NaiveDateTime)>,
+    ) -> Result<Lines<S>, Problem> {
+        let (start, end) = self.widened();
+        let (i, misordered) = find_start(
+            &mut source,
+            start,
+            end,
+            first,
+            last,
+            &parser,
+            self.tolerance,
+            probes,
+        )?;
+        let i = skip_early(&mut source, i, self.start, &parser)?;
+        let mut lines = show_from(source, i, self, parser)?;
+        lines.misordered = misordered;
+        Ok(lines)
+    }
+    // count the lines in the range as count does, looking for its start
+    // between the given line bounds and noting the lines probed with their
+    // timestamps
+    fn count_probed<S: LineSource>(
+        &self,
+        source: &mut S,
+        parser: &Parser,
+        first: Option<usize>,
+        last: Option<usize>,
+        probes: &mut Vec<(usize, NaiveDateTime)>,
+    ) -> Result<Extent, Problem> {
+        let (start, end) = self.widened();
+        let (i, mut misordered) = find_start(
+            source,
+            start,
+            end,
+            first,
+            last,
+            parser,
+            self.tolerance,
+            probes,
+        )?;
+        let i = skip_early(source, i, self.start, parser)?;
+        let stop = self
+            .end_line
+            .unwrap_or_else(|| source.len())
+            .min(source.len());
+        // the range ends at the first line at or after its end
+        let j = self.boundary(source, end, (i, stop), parser, probes, &mut misordered)?;
+        let bytes = (source.offset(i), source.offset(j.max(i)));
+        // a source that could not find an offset says so when next read
+        source
+            .read_at(bytes.1, &mut [])
+            .map_err(|e| Problem::io(source, e))?;
+        Ok(Extent {
+            first: i,
+            last: j.checked_sub(1).filter(|&j| j >= i),
+            bytes,
+            misordered,
+        })
+    }
+    // the index of the first line between the given indices timestamped at or
+    // after t, or the second index if there is none, noting the lines probed
+    // and any misordered regions met
+    fn boundary<S: LineSource>(
+        &self,
+        source: &mut S,
+        t: NaiveDateTime,
+        (i, stop): (usize, usize),
+        parser: &Parser,
+        probes: &mut Vec<(usize, NaiveDateTime)>,
+        misordered: &mut Vec<(usize, usize)>,
+    ) -> Result<usize, Problem> {
+        if i >= stop {
+            return Ok(i);
+        }
+        let found = find_start(
+            source,
+            t,
+            NaiveDateTime::MAX,
+            Some(i + 1),
+            Some(stop),
+            parser,
+            self.tolerance,
+            probes,
+        );
+        match found {
+            Ok((j, mut regions)) => {
+                misordered.append(&mut regions);
+                Ok(j)
+            }
+            Err(Problem::LogBefore { .. }) => Ok(stop),
+            Err(e) => Err(e),
+        }
+    }
+    // make a histogram of the range as histogram does, looking for its start
+    // between the given line bounds and noting the lines probed with their
+    // timestamps, and returning it with any misordered regions met
+    fn histogram_probed<S: LineSource>(
+        &self,
+        source: &mut S,
+        parser: Parser,
+        width: Duration,
+        first: Option<usize>,
+        last: Option<usize>,
+        probes: &mut Vec<(usize, NaiveDateTime)>,
+    ) -> Result<Histogram, Problem> {
+        let mut buckets = self.buckets(width)?;
+        let mut extent = self.count_probed(source, &parser, first, last, probes)?;
+        let end = extent.last.map_or(extent.first, |last| last + 1);
+        if extent.lines() > buckets.len() * BISECTED_BUCKET {
+            // find where each bucket ends
+            let mut i = extent.first;
+            for k in 0..buckets.len() {
+                let j = match buckets.get(k + 1) {
+                    Some(&(t, _)) => {
+                        self.boundary(source, t, (i, end), &parser, probes, &mut extent.misordered)?
+                    }
+                    None => end,
+                };
+                buckets[k].1 = j - i;
+                i = j;
+            }
+        } else {
+            // count each line in the bucket of its timestamp
+            let mut lines = show_from(&mut *source, extent.first, self, parser)?;
+            lines.stop = end;
+            while let Some(line) = lines.next() {
+                line?;
+                if let Some(t) = lines.time {
+                    let k = (seconds(t - self.start) / seconds(width)).max(0.0) as usize;
+                    let k = k.min(buckets.len() - 1);
+                    buckets[k].1 += 1;
+                }
+            }
+        }
+        Ok((buckets, extent.misordered))
+    }
+    // the start of each bucket of a histogram of the range, with no lines
+    // counted in any
+    fn buckets(&self, width: Duration) -> Result<Vec<(NaiveDateTime, usize)>, Problem> {
+        assert!(
+            width > Duration::zero(),
+            "a histogram's buckets must have a width"
+        );
+        // a width too great to multiply leaves room for any range
+        let most = width.checked_mul(MAX_BUCKETS as i32);
+        if most.is_some_and(|most| self.end - self.start > most) {
+            return Err(Problem::TooManyBuckets);
+        }
+        let mut buckets = vec![];
+        let mut t = Some(self.start);
+        while let Some(start) = t.filter(|&t| t < self.end) {
+            buckets.push((start, 0));
+            t = start.checked_add_signed(width);
+        }
+        Ok(buckets)
+    }
+    // the period within which lines in the range may be found, allowing for
+    // timestamps out of order by less than the tolerance
+    fn widened(&self) -> (NaiveDateTime, NaiveDateTime) {
+        let start = self.start.checked_sub_signed(self.tolerance);
+        let end = self.end.checked_add_signed(self.tolerance);
+        (
+            start.unwrap_or(NaiveDateTime::MIN),
+            end.unwrap_or(NaiveDateTime::MAX),
+        )
+    }
+    // this search, to be run over each log of a timeline
+    fn without_line_bounds(&self) -> Search {
+        Search {
+            start_line: None,
+            end_line: None,
+            ..self.clone()
+        }
+    }
+    // make sure the line bounds make sense for this source
+    fn check_bounds<S: LineSource>(&self, source: &S) -> Result<(), Problem> {
+        let bad = match (self.start_line, self.end_line) {
+            (Some(0), _) | (_, Some(0)) => true,
+            (Some(s), Some(e)) if s > e => true,
+            (Some(s), _) => s > source.len(),
+            _ => false,
+        };
+        if bad {
+            Err(Problem::BadLineBounds {
+                path: path_of(source),
+                start: self.start_line,
+                end: self.end_line,
+                lines: source.len(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// the default format compiled once and shared, since compiling it is not cheap
+fn default_format() -> Regex {
+    static RX: OnceLock<Regex> = OnceLock::new();
+    RX.get_or_init(|| Regex::new(DEFAULT_FORMAT).unwrap())
+        .clone()
+}
+
+// retained for compatibility with code written before Search existed
 #[doc(hidden)]
 pub fn fetch_lines(
-    mut larry: Larry,
+    larry: Larry,
     start: NaiveDateTime,
     end: NaiveDateTime,
     start_offset: Option<usize>,
     end_offset: Option<usize>,
-    rx: Regex,
+    rx: regex::Regex,
 ) -> Result<(usize, Vec<String>), Problem> {
-    let i1 = if start_offset.is_some() {
-        start_offset.unwrap() - 1
+    // the syntax of the two kinds of regex is the same
+    let mut search = Search::new(start, end).format(Regex::new(rx.as_str()).unwrap());
+    search.start_line = start_offset;
+    search.end_line = end_offset;
+    search.run(larry)
+}
+
+// make sure a log is not compressed, as one being written to cannot be
+fn followable(path: &Path) -> Result<(), Problem> {
+    if source::is_compressed(path).map_err(Problem::io_at(path))? {
+        let error = source::invalid("a compressed log cannot be followed");
+        return Err(Problem::io_at(path)(error));
+    }
+    Ok(())
+}
+
+// the path of a source, for use in problems
+fn path_of<S: LineSource>(source: &S) -> Option<PathBuf> {
+    source.path().map(Path::to_path_buf)
+}
+
+// when the file the source comes from was last modified, if it is known
+fn modified<S: LineSource>(source: &S) -> Option<SystemTime> {
+    let metadata = fs::metadata(source.path()?).ok()?;
+    metadata.modified().ok()
+}
+
+// the buckets of a histogram and the misordered regions met in making it
+type Histogram = (Vec<(NaiveDateTime, usize)>, Vec<(usize, usize)>);
+
+// the number of lines to a bucket of a histogram above which the boundaries
+// between buckets are found by bisection rather than by reading the lines
+const BISECTED_BUCKET: usize = 100;
+
+// the number of timestamps in order taken to mark the edge of a misordered
+// region
+const ORDERED_RUN: usize = 10;
+
+// what bisection finds
+enum Bisected {
+    // the index of the first line in the range
+    Start(usize),
+    // the indices of two lines whose timestamps are out of order
+    Misordered(usize, usize),
+}
+
+// find the index of the first line in the range, along with the first and
+// last indices of any misordered regions searched linearly to find it
+#[allow(clippy::too_many_arguments)]
+fn find_start<S: LineSource>(
+    source: &mut S,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    start_offset: Option<usize>,
+    end_offset: Option<usize>,
+    parser: &Parser,
+    tolerance: Duration,
+    probes: &mut Vec<(usize, NaiveDateTime)>,
+) -> Result<(usize, Vec<(usize, usize)>), Problem> {
+    let lo = start_offset.map(|o| o - 1).unwrap_or(0);
+    let hi = end_offset.unwrap_or(usize::MAX).min(source.len());
+    let mut beyond = None;
+    let (a, b) = match bisect(
+        source,
+        start,
+        end,
+        start_offset,
+        end_offset,
+        parser,
+        tolerance,
+        probes,
+    ) {
+        // bisection may not have met misordered lines near the start
+        Ok(Bisected::Start(i)) => (i, i),
+        Ok(Bisected::Misordered(a, b)) => narrow(source, a, b, parser, tolerance)?,
+        // the line last probed, at one end, is beyond the range, but it may
+        // belong to a misordered region holding lines in the range
+        Err(e @ Problem::LogAfter { .. }) | Err(e @ Problem::LogBefore { .. }) => {
+            let (i, _) = probes[probes.len() - 1];
+            beyond = Some(e);
+            (i, i)
+        }
+        Err(e) => return Err(e),
+    };
+    let (l, r) = localize(source, (a, b), (lo, hi), parser, tolerance)?;
+    if l == r {
+        // all is in order
+        return match beyond {
+            Some(e) => Err(e),
+            None => Ok((l, vec![])),
+        };
+    }
+    let mut regions = vec![(l, r)];
+    // the range may begin in the ordered lines before the region
+    let prior = match l.checked_sub(1) {
+        Some(i) if i >= lo => get_timestamp(source, i, parser, false)?,
+        _ => None,
+    };
+    if let Some((i, t)) = prior {
+        if i >= lo && t >= start {
+            let (i, mut before) = find_start(
+                source,
+                start,
+                end,
+                start_offset,
+                Some(l),
+                parser,
+                tolerance,
+                probes,
+            )?;
+            before.append(&mut regions);
+            return Ok((i, before));
+        }
+    }
+    // or in the region itself
+    let (mut early, mut late) = (false, false);
+    let mut i = l;
+    while i <= r {
+        let (j, t) = get_timestamp(source, i, parser, true)?.unwrap();
+        if t >= start && t < end {
+            return Ok((l, regions));
+        }
+        early |= t < start;
+        late |= t >= end;
+        i = j + 1;
+    }
+    // or else in the lines after it
+    let after = if r + 1 >= hi {
+        None
     } else {
-        0
+        Some(find_start(
+            source,
+            start,
+            end,
+            Some(r + 2),
+            end_offset,
+            parser,
+            tolerance,
+            probes,
+        ))
     };
-    if let Some((mut i1, mut t1)) = get_timestamp(&mut larry, i1, &rx, true) {
+    match after {
+        Some(Ok((i, mut after))) => {
+            regions.append(&mut after);
+            Ok((i, regions))
+        }
+        Some(Err(Problem::LogBefore { .. }))
+        | Some(Err(Problem::LogAfter { .. }))
+        | Some(Err(Problem::NoTimestamps { .. }))
+        | None => {
+            // there are no lines in the range; an end of the log beyond it
+            // stands for the whole log if the region is beyond it too
+            let after = after.and_then(Result::err);
+            match beyond {
+                Some(e @ Problem::LogAfter { .. })
+                    if !early && !matches!(after, Some(Problem::LogBefore { .. })) =>
+                {
+                    Err(e)
+                }
+                Some(e @ Problem::LogBefore { .. })
+                    if !late && !matches!(after, Some(Problem::LogAfter { .. })) =>
+                {
+                    Err(e)
+                }
+                _ => Ok((hi, regions)),
+            }
+        }
+        Some(Err(e)) => Err(e),
+    }
+}
+
+// find the index of the first line in the range by bisection, or a pair of
+// lines met along the way whose timestamps are misordered; the lines probed
+// are noted with their timestamps
+#[allow(clippy::too_many_arguments)]
+fn bisect<S: LineSource>(
+    source: &mut S,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    start_offset: Option<usize>,
+    end_offset: Option<usize>,
+    parser: &Parser,
+    tolerance: Duration,
+    probes: &mut Vec<(usize, NaiveDateTime)>,
+) -> Result<Bisected, Problem> {
+    let i1 = start_offset.map(|o| o - 1).unwrap_or(0);
+    if let Some((mut i1, mut t1)) = get_timestamp(source, i1, parser, true)? {
+        probes.push((i1, t1));
         if t1 > end {
-            return Err(Problem::LogAfter);
+            return Err(Problem::LogAfter {
+                path: path_of(source),
+            });
         }
-        let i2 = if end_offset.is_some() {
-            end_offset.unwrap() - 1
-        } else {
-            larry.len() - 1
-        };
-        if let Some((mut i2, mut t2)) = get_timestamp(&mut larry, i2, &rx, false) {
+        let i2 = end_offset
+            .map(|o| o - 1)
+            .unwrap_or(source.len() - 1)
+            .min(source.len() - 1);
+        if let Some((mut i2, mut t2)) = get_timestamp(source, i2, parser, false)? {
+            probes.push((i2, t2));
             if t2 < start {
-                return Err(Problem::LogBefore);
+                return Err(Problem::LogBefore {
+                    path: path_of(source),
+                });
             }
-            if t2 < t1 {
-                return Err(Problem::MisorderedTimestamps(
-                    i1,
-                    t1,
-                    larry.get(i1).unwrap().to_owned(),
-                    i2,
-                    t2,
-                    larry.get(i2).unwrap().to_owned(),
-                ));
+            if inverted(t1, t2, tolerance) {
+                // there is no ordered stretch to bisect
+                return Err(misordered(source, i1, t1, i2, t2));
             }
             if t1 >= start {
-                show_from(larry, i1, end, rx, end_offset)
+                Ok(Bisected::Start(i1))
             } else {
                 // find first line in range via binary search
                 loop {
                     if i2 - i1 < 10 {
                         // search linearly
                         let mut i = i1 + 1;
+                        let (mut i0, mut t0) = (i1, t1);
                         while i <= i2 {
-                            let (i3, t3) = get_timestamp(&mut larry, i, &rx, true).unwrap();
-                            if t3 < t1 {
-                                return Err(Problem::MisorderedTimestamps(
-                                    i1,
-                                    t1,
-                                    larry.get(i1).unwrap().to_owned(),
-                                    i3,
-                                    t3,
-                                    larry.get(i3).unwrap().to_owned(),
-                                ));
+                            let (i3, t3) = get_timestamp(source, i, parser, true)?.unwrap();
+                            probes.push((i3, t3));
+                            if inverted(t0, t3, tolerance) {
+                                return Ok(Bisected::Misordered(i0, i3));
                             }
-                            if t3 > t2 {
-                                return Err(Problem::MisorderedTimestamps(
-                                    i3,
-                                    t3,
-                                    larry.get(i3).unwrap().to_owned(),
-                                    i2,
-                                    t2,
-                                    larry.get(i2).unwrap().to_owned(),
-                                ));
+                            if inverted(t3, t2, tolerance) {
+                                return Ok(Bisected::Misordered(i3, i2));
                             }
                             if t3 >= start {
-                                return show_from(larry, i3, end, rx, end_offset);
+                                return Ok(Bisected::Start(i3));
                             }
+                            i0 = i3;
+                            t0 = t3;
                             i = i3 + 1;
                         }
                         unreachable!();
                     }
                     let i = estimate_index(&start, i1, &t1, i2, &t2);
-                    let (i3, t3) = get_timestamp(&mut larry, i, &rx, true).unwrap();
+                    let (i3, t3) = get_timestamp(source, i, parser, true)?.unwrap();
                     let mut flipped_once = false;
                     let (i3, t3) = if i3 == i2 {
                         // we've found two adjacent timestamps that bracket the start time
                         flipped_once = true;
-                        get_timestamp(&mut larry, i, &rx, false).unwrap()
+                        get_timestamp(source, i, parser, false)?.unwrap()
                     } else {
                         (i3, t3)
                     };
                     if flipped_once && i3 == i1 {
-                        return show_from(larry, i2, end, rx, end_offset);
+                        return Ok(Bisected::Start(i2));
                     }
-                    if t3 < t1 {
-                        return Err(Problem::MisorderedTimestamps(
-                            i1,
-                            t1,
-                            larry.get(i1).unwrap().to_owned(),
-                            i3,
-                            t3,
-                            larry.get(i3).unwrap().to_owned(),
-                        ));
+                    probes.push((i3, t3));
+                    if inverted(t1, t3, tolerance) {
+                        return Ok(Bisected::Misordered(i1, i3));
                     }
-                    if t3 > t2 {
-                        return Err(Problem::MisorderedTimestamps(
-                            i3,
-                            t3,
-                            larry.get(i3).unwrap().to_owned(),
-                            i2,
-                            t2,
-                            larry.get(i2).unwrap().to_owned(),
-                        ));
+                    if inverted(t3, t2, tolerance) {
+                        return Ok(Bisected::Misordered(i3, i2));
                     }
-                    if t3 == start {
-                        return show_from(larry, i3, end, rx, end_offset);
-                    } else if t3 < start {
+                    // lines before this one may have the same timestamp
+                    if t3 < start {
                         i1 = i3;
                         t1 = t3;
                     } else {
@@ -144,41 +1200,332 @@ pub fn fetch_lines(
             Err(Problem::NormallyUnreachable)
         }
     } else {
-        Err(Problem::NoTimestamps)
+        Err(Problem::NoTimestamps {
+            path: path_of(source),
+        })
     }
 }
 
-// show the lines after start index i up to a timestamp at or after end
-fn show_from(
-    mut larry: Larry,
-    i: usize,
+// narrow a pair of lines whose timestamps are misordered down to a pair with
+// no timestamped line between them
+fn narrow<S: LineSource>(
+    source: &mut S,
+    a: usize,
+    b: usize,
+    parser: &Parser,
+    tolerance: Duration,
+) -> Result<(usize, usize), Problem> {
+    let (mut a, mut ta) = get_timestamp(source, a, parser, true)?.unwrap();
+    let (mut b, mut tb) = get_timestamp(source, b, parser, false)?.unwrap();
+    loop {
+        let m = a + (b - a) / 2;
+        let (mut i, mut t) = get_timestamp(source, m, parser, true)?.unwrap();
+        if i == b {
+            let (j, u) = get_timestamp(source, m, parser, false)?.unwrap();
+            i = j;
+            t = u;
+        }
+        if i == a {
+            return Ok((a, b));
+        }
+        // at least one half holds an inversion; prefer one the tolerance
+        // does not excuse
+        if inverted(ta, t, tolerance) || (!inverted(t, tb, tolerance) && ta - t >= t - tb) {
+            b = i;
+            tb = t;
+        } else {
+            a = i;
+            ta = t;
+        }
+    }
+}
+
+// widen a bracket of lines within which timestamps are misordered until it is
+// bounded on either side by a run of timestamps in order or the line bounds,
+// returning the indices of the first and last misordered lines
+fn localize<S: LineSource>(
+    source: &mut S,
+    (a, b): (usize, usize),
+    (lo, hi): (usize, usize),
+    parser: &Parser,
+    tolerance: Duration,
+) -> Result<(usize, usize), Problem> {
+    let (mut l, mut later) = get_timestamp(source, a, parser, true)?.unwrap();
+    let mut i = l;
+    let mut run = 0;
+    while run < ORDERED_RUN && i > lo {
+        match get_timestamp(source, i - 1, parser, false)? {
+            Some((j, t)) if j >= lo => {
+                if inverted(t, later, tolerance) {
+                    l = j;
+                    run = 0;
+                } else {
+                    run += 1;
+                }
+                later = t;
+                i = j;
+            }
+            _ => break,
+        }
+    }
+    let (mut r, mut earlier) = get_timestamp(source, b, parser, false)?.unwrap();
+    let mut i = r + 1;
+    let mut run = 0;
+    while run < ORDERED_RUN && i < hi {
+        match get_timestamp(source, i, parser, true)? {
+            Some((j, t)) if j < hi => {
+                if inverted(earlier, t, tolerance) {
+                    r = j;
+                    run = 0;
+                } else {
+                    run += 1;
+                }
+                earlier = t;
+                i = j + 1;
+            }
+            _ => break,
+        }
+    }
+    Ok((l, r))
+}
+
+// whether a timestamp is too far before one earlier in the log
+fn inverted(earlier: NaiveDateTime, later: NaiveDateTime, tolerance: Duration) -> bool {
+    later < earlier && earlier - later >= tolerance
+}
+
+fn misordered<S: LineSource>(
+    source: &mut S,
+    i1: usize,
+    t1: NaiveDateTime,
+    i2: usize,
+    t2: NaiveDateTime,
+) -> Problem {
+    Problem::MisorderedTimestamps {
+        path: path_of(source),
+        first: (i1, t1, lossy(source.line(i1).unwrap_or_default())),
+        second: (i2, t2, lossy(source.line(i2).unwrap_or_default())),
+    }
+}
+
+/// Where the lines found by [`Search::count`] are in a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extent {
+    /// The index of the first line in the range or, if there are none, of the
+    /// line at which it would begin.
+    pub first: usize,
+    /// The index of the last line in the range, if there are any.
+    pub last: Option<usize>,
+    /// The byte offsets at which the lines in the range begin and end.
+    pub bytes: (u64, u64),
+    /// The indices of the first and last lines of each region where
+    /// timestamps were found out of order, as by [`Lines::misordered`].
+    pub misordered: Vec<(usize, usize)>,
+}
+
+impl Extent {
+    /// The number of lines in the range.
+    pub fn lines(&self) -> usize {
+        self.last.map_or(0, |last| last + 1 - self.first)
+    }
+}
+
+/// A lazy iterator over the lines found by [`Search::iter`].
+///
+/// Lines are yielded as the bytes found in the log, including their line
+/// terminators.
+pub struct Lines<S> {
+    source: S,
+    offset: usize,
+    next: usize,
+    stop: usize,
+    last: Option<usize>,
+    misordered: Vec<(usize, usize)>,
+    buf: Vec<u8>,
+    buf_start: u64,
+    start: NaiveDateTime,
     end: NaiveDateTime,
-    rx: Regex,
-    end_offset: Option<usize>,
-) -> Result<(usize, Vec<String>), Problem> {
-    let offset = larry.offset(i).unwrap();
-    let mut end_offset = if let Some(o) = end_offset {
-        o
-    } else {
-        larry.len()
-    };
-    end_offset -= i;
-    larry.file.seek(SeekFrom::Start(offset)).ok();
-    let reader = BufReader::new(larry.file);
-    let mut vec = vec![];
-    for (i, line) in reader.lines().enumerate() {
-        if i == end_offset {
-            break;
+    // no line at or after this time can be in the range
+    stop_time: NaiveDateTime,
+    // whether the line last timestamped is in the range
+    keep: bool,
+    // the timestamp of the line last timestamped
+    time: Option<NaiveDateTime>,
+    tolerance: Duration,
+    parser: Parser,
+    done: bool,
+}
+
+// the number of bytes to read from the source at once
+const CHUNK_SIZE: usize = 1 << 16;
+
+impl<S: LineSource> Lines<S> {
+    /// The index of the first line in the range.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// The index of the line last yielded, if any has been.
+    pub fn last_index(&self) -> Option<usize> {
+        self.last
+    }
+    /// The indices of the first and last lines of each region where
+    /// timestamps were found out of order while looking for the start of the
+    /// range. Such regions are searched line by line rather than bisected.
+    pub fn misordered(&self) -> &[(usize, usize)] {
+        &self.misordered
+    }
+    /// The path of the log the lines come from, if it has one.
+    pub fn path(&self) -> Option<&Path> {
+        self.source.path()
+    }
+    // the bytes of the next line, reading more from the source as needed
+    fn next_line(&mut self) -> io::Result<&[u8]> {
+        let start = self.source.offset(self.next);
+        let end = self.source.offset(self.next + 1);
+        if start < self.buf_start || end > self.buf_start + self.buf.len() as u64 {
+            let wanted = CHUNK_SIZE.max((end - start) as usize);
+            self.buf.resize(wanted, 0);
+            let mut filled = 0;
+            while filled < wanted {
+                match self
+                    .source
+                    .read_at(start + filled as u64, &mut self.buf[filled..])
+                {
+                    Ok(0) => break,
+                    Ok(n) => filled += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            self.buf.truncate(filled);
+            self.buf_start = start;
+            if end > start + filled as u64 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "source is shorter than its line index",
+                ));
+            }
         }
-        let s = line.unwrap();
-        if let Some(nd) = timestamp(s.as_ref(), &rx) {
-            if nd >= end {
-                break;
+        let a = (start - self.buf_start) as usize;
+        let b = (end - self.buf_start) as usize;
+        Ok(&self.buf[a..b])
+    }
+}
+
+impl<S: LineSource> Iterator for Lines<S> {
+    type Item = Result<Vec<u8>, Problem>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done || self.next >= self.stop {
+                return None;
             }
+            let line = self.next_line().map(|bytes| bytes.to_vec());
+            let line = line.map_err(|e| Problem::io(&self.source, e));
+            match line {
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                Ok(s) => {
+                    if let Some(nd) = self.parser.timestamp(&s) {
+                        let past_misordered = self.misordered.iter().all(|&(_, r)| self.next > r);
+                        if nd >= self.stop_time && past_misordered {
+                            // the range ends here unless the lines that follow
+                            // are out of order
+                            match localize(
+                                &mut self.source,
+                                (self.next, self.next),
+                                (self.next, self.stop),
+                                &self.parser,
+                                self.tolerance,
+                            ) {
+                                Ok((_, r)) if r > self.next => self.misordered.push((self.next, r)),
+                                Ok(_) => {
+                                    self.done = true;
+                                    return None;
+                                }
+                                Err(e) => {
+                                    self.done = true;
+                                    return Some(Err(e));
+                                }
+                            }
+                        }
+                        // with a tolerance, lines just out of the range may
+                        // be interleaved with those in it
+                        self.keep = nd >= self.start && nd < self.end;
+                        self.time = Some(nd);
+                    }
+                    let i = self.next;
+                    self.next += 1;
+                    if self.keep {
+                        self.last = Some(i);
+                        return Some(Ok(s));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// strip the line terminator from the end of a line
+fn trim_terminator(line: &[u8]) -> &[u8] {
+    let mut line = line;
+    if line.last() == Some(&b'\n') {
+        line = &line[..line.len() - 1];
+    }
+    if line.last() == Some(&b'\r') {
+        line = &line[..line.len() - 1];
+    }
+    line
+}
+
+// skip any lines from index i timestamped before the start, as lines out of
+// order by less than the tolerance may be
+fn skip_early<S: LineSource>(
+    source: &mut S,
+    i: usize,
+    start: NaiveDateTime,
+    parser: &Parser,
+) -> Result<usize, Problem> {
+    let mut i = i;
+    loop {
+        match get_timestamp(source, i, parser, true)? {
+            Some((j, t)) if t < start => i = j + 1,
+            Some((j, _)) => return Ok(j),
+            None => return Ok(source.len()),
         }
-        vec.push(s);
     }
-    Ok((i, vec))
+}
+
+// iterate over the lines in the search's range from start index i
+fn show_from<S: LineSource>(
+    source: S,
+    i: usize,
+    search: &Search,
+    parser: Parser,
+) -> Result<Lines<S>, Problem> {
+    let stop = search
+        .end_line
+        .unwrap_or_else(|| source.len())
+        .min(source.len());
+    Ok(Lines {
+        source,
+        offset: i,
+        next: i,
+        stop,
+        last: None,
+        misordered: vec![],
+        buf: vec![],
+        buf_start: 0,
+        start: search.start,
+        end: search.end,
+        stop_time: search.widened().1,
+        keep: true,
+        time: None,
+        tolerance: search.tolerance,
+        parser,
+        done: false,
+    })
 }
 
 // estimate the index of time t given the indices of times t1 and t2
@@ -193,9 +1540,7 @@ fn estimate_index(
         // at this point t cannot be after t2
         i1
     } else {
-        let numerator = t.timestamp() - t1.timestamp();
-        let denominator = t2.timestamp() - t1.timestamp();
-        let f = numerator as f64 / denominator as f64;
+        let f = seconds(*t - *t1) / seconds(*t2 - *t1);
         let n = (i2 + 1 - i1) as f64;
         let estimate = i1 + (n * f).round() as usize;
         // we know it is not either end point
@@ -209,98 +1554,38 @@ fn estimate_index(
     }
 }
 
-fn get_timestamp(
-    larry: &mut Larry,
-    i: usize,
-    time_format: &Regex,
-    down: bool,
-) -> Option<(usize, NaiveDateTime)> {
-    let mut i = i;
-    loop {
-        match larry.get(i) {
-            Ok(s) => {
-                if let Some(nd) = timestamp(s.as_ref(), time_format) {
-                    return Some((i, nd));
-                }
-                if down {
-                    i += 1
-                } else if i == 0 {
-                    return None;
-                } else {
-                    i -= 1
-                }
-            }
-            Err(_) => return None,
-        }
+// a duration in seconds, keeping any fraction
+fn seconds(d: Duration) -> f64 {
+    match d.num_microseconds() {
+        Some(us) => us as f64 / 1_000_000.0,
+        None => d.num_seconds() as f64,
     }
 }
 
-fn timestamp(line: &str, time_format: &Regex) -> Option<NaiveDateTime> {
-    if let Some(captures) = time_format.captures(line) {
-        let mut y = 0;
-        let m;
-        let d;
-        let h;
-        let mn;
-        let s;
-        if let Some(year) = captures.name("year") {
-            if let Ok(year) = year.as_str().parse::<i32>() {
-                y = year;
-            }
-        } else {
-            return None;
-        }
-        if let Some(month) = captures.name("month") {
-            if let Ok(month) = month.as_str().parse::<u32>() {
-                m = month;
-            } else {
-                return None;
-            }
-        } else {
-            return None;
-        }
-        if let Some(day) = captures.name("day") {
-            if let Ok(day) = day.as_str().parse::<u32>() {
-                d = day;
-            } else {
-                return None;
-            }
-        } else {
-            return None;
-        }
-        if let Some(hour) = captures.name("hour") {
-            if let Ok(hour) = hour.as_str().parse::<u32>() {
-                h = hour;
-            } else {
-                return None;
-            }
-        } else {
-            return None;
-        }
-        if let Some(minute) = captures.name("minute") {
-            if let Ok(minute) = minute.as_str().parse::<u32>() {
-                mn = minute;
-            } else {
-                return None;
-            }
-        } else {
-            return None;
-        }
-        if let Some(second) = captures.name("second") {
-            if let Ok(second) = second.as_str().parse::<u32>() {
-                s = second;
-            } else {
-                return None;
-            }
-        } else {
-            return None;
+fn get_timestamp<S: LineSource>(
+    source: &mut S,
+    i: usize,
+    parser: &Parser,
+    down: bool,
+) -> Result<Option<(usize, NaiveDateTime)>, Problem> {
+    let mut i = i;
+    while i < source.len() {
+        let s = source.line(i).map_err(|e| Problem::io(source, e))?;
+        if let Some(nd) = parser.timestamp(&s) {
+            return Ok(Some((i, nd)));
         }
-        if let Some(nd) = NaiveDate::from_ymd_opt(y, m, d) {
-            nd.and_hms_opt(h, mn, s)
+        if down {
+            i += 1
+        } else if i == 0 {
+            break;
         } else {
-            None
+            i -= 1
         }
-    } else {
-        None
     }
+    Ok(None)
+}
+
+// a line as text, with any bytes that are not UTF-8 replaced
+fn lossy(line: Vec<u8>) -> String {
+    String::from_utf8_lossy(&line).into_owned()
 }
@@ -1,16 +1,22 @@
 #[macro_use]
 extern crate clap;
 use clap::{App, ArgMatches};
-use std::path::Path;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+extern crate chrono;
+use chrono::{Duration, NaiveDateTime};
 extern crate two_timer;
-use two_timer::parse;
-extern crate larry;
-use larry::Larry;
+use two_timer::{parse, Config};
 extern crate regex;
-use regex::Regex;
+use regex::bytes::Regex;
 extern crate trufflehunter;
-use trufflehunter::{fetch_lines, Problem, DEFAULT_FORMAT};
+use trufflehunter::{
+    detect_format, logs_in, open_indexed, open_log, parse_format, preset, rotated_logs, Extent,
+    LineSource, Lines, Periods, Problem, Search, Zone, DEFAULT_FORMAT, MAX_BUCKETS, PRESETS,
+    PRESET_ALIASES,
+};
 
 fn main() {
     let options = app().get_matches();
@@ -19,120 +25,530 @@ fn main() {
         println!("\n\n{}", long_help());
         process::exit(0)
     }
-    if let Some(file_name) = options.value_of("LOG") {
-        if let Some(values) = options.values_of("WHEN") {
-            let expr = values.collect::<Vec<&str>>().join(" ");
-            match parse(&expr, None) {
-                Ok((d1, d2, _)) => {
-                    match Larry::new(Path::new(file_name)) {
-                        Ok(larry) => {
-                            let pat = options.value_of("format").unwrap_or(DEFAULT_FORMAT);
-                            match Regex::new(pat) {
-                                Err(error) => usage(
-                                    &format!("problem with format \"{}\": {}", pat, error),
-                                    options,
-                                ),
-                                Ok(rx) => {
-                                    let start_offset = if let Some(s) = options.value_of("start") {
-                                        match s.parse::<usize>() {
-                                            Err(_) => {
-                                                return usage(
-                                                    &format!(
-                                                    "cannot parse --start-line {} as a line number",
-                                                    s
-                                                ),
-                                                    options,
-                                                );
-                                            }
-                                            Ok(v) => {
-                                                if v == 0 {
-                                                    return usage(
-                                                        "--start-line must be greater than 0",
-                                                        options,
-                                                    );
-                                                }
-                                                Some(v)
-                                            }
-                                        }
-                                    } else {
-                                        None
-                                    };
-                                    let end_offset = if let Some(e) = options.value_of("end") {
-                                        match e.parse::<usize>() {
-                                            Err(_) => {
-                                                return usage(&format!("cannot parse --end-line {} as a line number", e), options);
-                                            }
-                                            Ok(v) => {
-                                                if v == 0 {
-                                                    return usage(
-                                                        "--end-line must be greater than 0",
-                                                        options,
-                                                    );
-                                                }
-                                                Some(v)
-                                            }
-                                        }
-                                    } else {
-                                        None
-                                    };
-                                    if start_offset.is_some()
-                                        && end_offset.is_some()
-                                        && start_offset.unwrap() > end_offset.unwrap()
-                                    {
-                                        let start_offset = start_offset.unwrap();
-                                        let end_offset = end_offset.unwrap();
-                                        return usage(
-                                            &format!(
-                                                "--start-line {} is greater than --end-line {}",
-                                                start_offset, end_offset
-                                            ),
-                                            options,
-                                        );
-                                    }
-                                    if options.is_present("verbose") {
-                                        println!("searching for events in the range {} - {}", d1, d2);
-                                    }
-                                    match fetch_lines(larry, d1, d2, start_offset, end_offset, rx) {
-                                    Err(p) => match p {
-                                        Problem::NoTimestamps => eprintln!("no timestamps found"),
-                                        Problem::LogAfter => eprintln!("events in log are after period sought"),
-                                        Problem::LogBefore => eprintln!("events in log are before period sought"),
-                                        Problem::MisorderedTimestamps(i1, t1, l1, i2, t2, l2) => {
-                                            eprintln!("the timestamp on line {}, {}, is misordered relative to that on line {}, {}\nline {}: {}line {}: {}", i1, t1, i2, t2, i1, l1, i2, l2)
-                                        }
-                                        Problem::NormallyUnreachable => unreachable!()
-                                    },
-                                    Ok((offset, lines)) => {
-                                        if options.is_present("verbose") {
-                                            if lines.len() == 0 {
-                                                println!("no events found");
-                                            } else {
-                                                println!("lines {} - {}", offset, offset + lines.len() - 1);
-                                            }
-                                        }
-                                        for line in lines {
-                                            println!("{}", line);
-                                        }
-                                    }
-                                }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            usage(&format!("problem with file {}: {}", file_name, e), options)
-                        }
-                    }
-                }
+    if options.is_present("list_presets") {
+        for p in PRESETS {
+            let aliases: Vec<&str> = PRESET_ALIASES
+                .iter()
+                .filter(|&&(_, name)| name == p.name)
+                .map(|&(alias, _)| alias)
+                .collect();
+            let name = if aliases.is_empty() {
+                p.name.to_string()
+            } else {
+                format!("{} (also {})", p.name, aliases.join(", "))
+            };
+            println!(
+                "{}\n    {}\n    format: {}\n    sample: {}\n",
+                name, p.description, p.format, p.sample
+            );
+        }
+        process::exit(0)
+    }
+    let dir = options.value_of("dir");
+    let mut words: Vec<&str> = match options.values_of("WHEN") {
+        Some(values) => values.collect(),
+        None => vec![],
+    };
+    let file_name = match (dir, options.value_of("LOG")) {
+        // with --dir there is no log argument, so it begins the time expression
+        (Some(_), Some(word)) => {
+            words.insert(0, word);
+            None
+        }
+        (Some(_), None) => None,
+        (None, Some(file_name)) => Some(file_name),
+        (None, None) => usage("no log file provided", &options),
+    };
+    let mut exprs = vec![];
+    if !words.is_empty() {
+        exprs.push(words.join(" "));
+    }
+    if let Some(values) = options.values_of("when") {
+        exprs.extend(values.map(String::from));
+    }
+    if let Some(file) = options.value_of("when_file") {
+        match fs::read_to_string(file) {
+            Ok(text) => exprs.extend(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !(line.is_empty() || line.starts_with('#')))
+                    .map(String::from),
+            ),
+            Err(e) => usage(&format!("problem with file {}: {}", file, e), &options),
+        }
+    }
+    if exprs.is_empty() {
+        usage("no time expression provided", &options);
+    }
+    let log_zone = zone(&options, "log_zone", "--log-zone");
+    let tolerance = duration(&options, "tolerance", "--tolerance");
+    let width = duration(&options, "histogram", "--histogram");
+    if width.is_some_and(|w| w <= Duration::zero()) {
+        usage("--histogram must be greater than 0", &options);
+    }
+    let query_zone = zone(&options, "query_zone", "--query-zone");
+    let periods: Vec<(NaiveDateTime, NaiveDateTime)> = exprs
+        .iter()
+        .map(|expr| {
+            let config = query_zone.map(|z| Config::new().now(z.now()));
+            match parse(expr, config) {
+                Ok((d1, d2, _)) => (d1, d2),
                 Err(e) => usage(
                     &format!("problem with time \"{}\": {}", expr, e.msg()),
-                    options,
+                    &options,
+                ),
+            }
+        })
+        .collect();
+    if let Some(width) = width {
+        let most = width.checked_mul(MAX_BUCKETS as i32);
+        if periods
+            .iter()
+            .any(|&(d1, d2)| most.is_some_and(|most| d2 - d1 > most))
+        {
+            usage(
+                &format!(
+                    "--histogram {} would divide the period into more than {} parts",
+                    options.value_of("histogram").unwrap(),
+                    MAX_BUCKETS
                 ),
+                &options,
+            );
+        }
+    }
+    // a trailing * asks for the log together with its rotated predecessors
+    let rotated = file_name.and_then(|f| f.strip_suffix('*'));
+    let paths = if let Some(dir) = dir {
+        match logs_in(dir) {
+            Ok(paths) => paths,
+            Err(e) => usage(&format!("problem with directory {}: {}", dir, e), &options),
+        }
+    } else if let Some(base) = rotated {
+        match rotated_logs(base) {
+            Ok(paths) => paths,
+            Err(e) => usage(
+                &format!("problem with logs {}: {}", file_name.unwrap(), e),
+                &options,
+            ),
+        }
+    } else {
+        vec![PathBuf::from(file_name.unwrap())]
+    };
+    if paths.is_empty() {
+        let msg = match dir {
+            Some(dir) => format!("no logs found in {}", dir),
+            None => format!("no logs match {}", file_name.unwrap()),
+        };
+        usage(&msg, &options);
+    }
+    let several = dir.is_some() || rotated.is_some();
+    let index = options.is_present("index");
+    if several && index {
+        usage(
+            "--index cannot be used with rotated logs or --dir",
+            &options,
+        );
+    }
+    let follow = options.is_present("follow");
+    if several && follow {
+        usage(
+            "--follow cannot be used with rotated logs or --dir",
+            &options,
+        );
+    }
+    if periods.len() > 1 && follow {
+        usage("--follow cannot be used with several periods", &options);
+    }
+    let count = options.is_present("count");
+    if several && count {
+        usage(
+            "--count cannot be used with rotated logs or --dir",
+            &options,
+        );
+    }
+    if several && width.is_some() {
+        usage(
+            "--histogram cannot be used with rotated logs or --dir",
+            &options,
+        );
+    }
+    // a single log is opened at once, unless it is followed or its lines are
+    // found with its index; of several, only those needed are
+    let mut source = if several || follow || (index && !count && width.is_none()) {
+        None
+    } else {
+        Some(open(&paths[0], &options))
+    };
+    let formats = if let Some(specs) = options.values_of("format") {
+        specs
+            .map(|spec| match parse_format(spec) {
+                Ok(rx) => rx,
+                Err(error) => usage(
+                    &format!("problem with format \"{}\": {}", spec, error),
+                    &options,
+                ),
+            })
+            .collect()
+    } else if let Some(name) = options.value_of("preset") {
+        match preset(name) {
+            Some(p) => vec![Regex::new(p.format).unwrap()],
+            None => usage(
+                &format!(
+                    "unknown preset \"{}\"; --list-presets lists the presets",
+                    name
+                ),
+                &options,
+            ),
+        }
+    } else if options.is_present("detect") {
+        let detected = match source.as_mut() {
+            Some(source) => detect_format(source),
+            None => {
+                // any of several logs will do, so long as it has timestamps
+                let mut detected = None;
+                for path in &paths {
+                    let result = detect_format(&mut open(path, &options));
+                    let found = result.is_ok();
+                    detected = Some(result);
+                    if found {
+                        break;
+                    }
+                }
+                detected.unwrap()
+            }
+        };
+        match detected {
+            Ok(p) => {
+                if options.is_present("verbose") {
+                    println!("detected the {} format: {}", p.name, p.format);
+                }
+                vec![Regex::new(p.format).unwrap()]
+            }
+            Err(p) => {
+                eprintln!("{}", p);
+                process::exit(1)
+            }
+        }
+    } else {
+        vec![Regex::new(DEFAULT_FORMAT).unwrap()]
+    };
+    let start_offset = line_number(&options, "start", "--start-line");
+    let end_offset = line_number(&options, "end", "--end-line");
+    if let (Some(start_offset), Some(end_offset)) = (start_offset, end_offset) {
+        if start_offset > end_offset {
+            usage(
+                &format!(
+                    "--start-line {} is greater than --end-line {}",
+                    start_offset, end_offset
+                ),
+                &options,
+            );
+        }
+    }
+    if several && (start_offset.is_some() || end_offset.is_some()) {
+        usage(
+            "--start-line and --end-line cannot be used with rotated logs or --dir",
+            &options,
+        );
+    }
+    let (d1, d2) = periods[0];
+    let mut search = Search::new(d1, d2).formats(formats);
+    if let Some(n) = start_offset {
+        search = search.start_line(n);
+    }
+    if let Some(n) = end_offset {
+        search = search.end_line(n);
+    }
+    if let Some(tolerance) = tolerance {
+        search = search.tolerance(tolerance);
+    }
+    if let Some(zone) = log_zone {
+        search = search.log_zone(zone);
+    }
+    if let Some(zone) = query_zone {
+        search = search.query_zone(zone);
+    }
+    let verbose = options.is_present("verbose");
+    let label = options.is_present("label");
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut terminated = true;
+    // a single log is searched for every period once opened, each search
+    // beginning from what earlier ones found
+    let mut source = source.map(Periods::new);
+    for &(d1, d2) in &periods {
+        if verbose {
+            writeln!(out, "searching for events in the range {} - {}", d1, d2).ok();
+        }
+        let search = search.clone().period(d1, d2);
+        let mut shown = Shown::default();
+        if follow {
+            let followed = if index {
+                search.follow_indexed(&paths[0])
+            } else {
+                search.follow(&paths[0])
+            };
+            let mut followed = match followed {
+                Ok(followed) => followed,
+                Err(p) => {
+                    eprintln!("{}", p);
+                    process::exit(1)
+                }
+            };
+            let prefix = if label {
+                format!("{}: ", followed.path().display())
+            } else {
+                String::new()
+            };
+            for line in followed.by_ref() {
+                match line {
+                    Ok(line) => terminated = print(&mut out, &prefix, &line, terminated),
+                    Err(p) => {
+                        eprintln!("{}", p);
+                        process::exit(1)
+                    }
+                }
+            }
+            for &(a, b) in followed.misordered() {
+                shown.regions.push((None, a, b));
+            }
+            for &(first, last) in followed.ranges() {
+                shown.ranges.push((None, first, last));
+            }
+        } else if let Some(source) = source.as_mut().filter(|_| count) {
+            let extent = found(source.count(&search), periods.len());
+            writeln!(out, "{}", extent.as_ref().map_or(0, Extent::lines)).ok();
+            if let Some(extent) = extent {
+                for (a, b) in extent.misordered {
+                    shown.regions.push((None, a, b));
+                }
+                if let Some(last) = extent.last {
+                    shown.ranges.push((None, extent.first, last));
+                    shown.bytes = Some(extent.bytes);
+                }
+            }
+        } else if let (Some(source), Some(width)) = (source.as_mut(), width) {
+            let buckets = found(source.histogram(&search, width), periods.len());
+            if let Some(buckets) = buckets {
+                draw(&mut out, &buckets, options.is_present("bar"));
+                // the buckets say all there is to say about the lines found
+                continue;
+            }
+        } else if let Some(source) = source.as_mut() {
+            if let Some(lines) = found(source.iter(&search), periods.len()) {
+                terminated = show(&mut out, lines, label, terminated, &mut shown);
             }
         } else {
-            usage("no time expression provided", options);
+            let found = if index {
+                found(search.iter_indexed(&paths[0]), periods.len()).map(|lines| vec![lines])
+            } else {
+                found(search.iter_paths(&paths), periods.len())
+            };
+            for lines in found.unwrap_or_default() {
+                terminated = show(&mut out, lines, label, terminated, &mut shown);
+            }
         }
+        if verbose {
+            if !terminated {
+                // the log did not end with a newline
+                writeln!(out).ok();
+                terminated = true;
+            }
+            for (path, a, b) in shown.regions {
+                if let Some(path) = path.filter(|_| several) {
+                    write!(out, "{}: ", path).ok();
+                }
+                writeln!(
+                    out,
+                    "lines {} - {} are misordered; they were searched line by line",
+                    a, b
+                )
+                .ok();
+            }
+            if shown.ranges.is_empty() {
+                writeln!(out, "no events found").ok();
+            }
+            for (path, offset, last) in shown.ranges {
+                match path {
+                    Some(path) if several => {
+                        writeln!(out, "{}: lines {} - {}", path, offset, last).ok()
+                    }
+                    _ => writeln!(out, "lines {} - {}", offset, last).ok(),
+                };
+            }
+            if let Some((a, b)) = shown.bytes {
+                writeln!(out, "bytes {} - {}", a, b).ok();
+            }
+        }
+    }
+}
+
+// the misordered regions and the ranges of lines found in a period, each with
+// the path of its log, and the bytes the lines span if they were only counted
+#[derive(Default)]
+struct Shown {
+    regions: Vec<(Option<String>, usize, usize)>,
+    ranges: Vec<(Option<String>, usize, usize)>,
+    bytes: Option<(u64, u64)>,
+}
+
+// what a search found or, if it found nothing in a period before or after the
+// log and there are other periods to search for, None; any other problem ends
+// the search
+fn found<T>(result: Result<T, Problem>, periods: usize) -> Option<T> {
+    match result {
+        Ok(found) => Some(found),
+        Err(Problem::LogBefore { .. }) | Err(Problem::LogAfter { .. }) if periods > 1 => None,
+        Err(p) => {
+            eprintln!("{}", p);
+            process::exit(1)
+        }
+    }
+}
+
+// print the lines found in a log, noting the misordered regions and the range
+// of lines found, and returning whether the last line printed was terminated
+fn show<W: Write, S: LineSource>(
+    out: &mut W,
+    mut lines: Lines<S>,
+    label: bool,
+    mut terminated: bool,
+    shown: &mut Shown,
+) -> bool {
+    let offset = lines.offset();
+    let path = lines.path().map(|p| p.display().to_string());
+    let prefix = match &path {
+        Some(path) if label => format!("{}: ", path),
+        _ => String::new(),
+    };
+    for line in lines.by_ref() {
+        match line {
+            Ok(line) => terminated = print(out, &prefix, &line, terminated),
+            Err(p) => {
+                eprintln!("{}", p);
+                process::exit(1)
+            }
+        }
+    }
+    for &(a, b) in lines.misordered() {
+        shown.regions.push((path.clone(), a, b));
+    }
+    if let Some(last) = lines.last_index() {
+        shown.ranges.push((path, offset, last));
+    }
+    terminated
+}
+
+// the length of the bar drawn for the fullest bucket of a histogram
+const BAR_LENGTH: usize = 50;
+
+// print the start of each bucket of a histogram and the number of lines in it,
+// along with a bar if asked for one
+fn draw<W: Write>(out: &mut W, buckets: &[(NaiveDateTime, usize)], bar: bool) {
+    let most = buckets.iter().map(|&(_, n)| n).max().unwrap_or(0);
+    let digits = most.to_string().len();
+    for &(t, n) in buckets {
+        let mut row = format!("{}  {:>digits$}", t, n, digits = digits);
+        if bar && n > 0 {
+            // any bucket with lines in it gets some bar
+            row += "  ";
+            row += &"#".repeat((n * BAR_LENGTH).div_ceil(most));
+        }
+        written(writeln!(out, "{}", row));
+    }
+}
+
+// print a line with its prefix, returning whether it was terminated; the line
+// before it is terminated if it was not, to keep the last line of one log from
+// running into the next
+fn print<W: Write>(out: &mut W, prefix: &str, line: &[u8], terminated: bool) -> bool {
+    let result = if terminated {
+        Ok(())
     } else {
-        usage("no log file provided", options);
+        out.write_all(b"\n")
+    };
+    written(
+        result
+            .and_then(|_| out.write_all(prefix.as_bytes()))
+            .and_then(|_| out.write_all(line)),
+    );
+    line.ends_with(b"\n") || line.ends_with(b"\r")
+}
+
+// stop quietly if output could not be written, as is most likely because it
+// was piped to a program that has exited
+fn written(result: io::Result<()>) {
+    if result.is_err() {
+        process::exit(0)
+    }
+}
+
+// open a log or explain why it cannot be
+fn open(path: &Path, options: &ArgMatches) -> Box<dyn LineSource> {
+    let opened = if options.is_present("index") {
+        open_indexed(path)
+    } else {
+        open_log(path)
+    };
+    match opened {
+        Ok(source) => source,
+        Err(e) => usage(
+            &format!("problem with file {}: {}", path.display(), e),
+            options,
+        ),
+    }
+}
+
+// parse a line number option
+fn line_number(options: &ArgMatches, name: &str, flag: &str) -> Option<usize> {
+    let n = options.value_of(name)?;
+    match n.parse::<usize>() {
+        Err(_) => usage(
+            &format!("cannot parse {} {} as a line number", flag, n),
+            options,
+        ),
+        Ok(0) => usage(&format!("{} must be greater than 0", flag), options),
+        Ok(v) => Some(v),
+    }
+}
+
+// parse a duration option, a number of days, hours, minutes, seconds, or
+// milliseconds such as "2s" or "500ms"
+fn duration(options: &ArgMatches, name: &str, flag: &str) -> Option<Duration> {
+    let t = options.value_of(name)?;
+    let split = t.find(|c: char| !(c.is_ascii_digit() || c == '.'));
+    let (n, unit) = t.split_at(split.unwrap_or(t.len()));
+    let millis = match unit.trim() {
+        "ms" => 1.0,
+        "" | "s" => 1000.0,
+        "m" => 60_000.0,
+        "h" => 3_600_000.0,
+        "d" => 86_400_000.0,
+        _ => usage(
+            &format!(
+                "cannot parse {} {}; the units are d, h, m, s, and ms",
+                flag, t
+            ),
+            options,
+        ),
+    };
+    let micros = match n.parse::<f64>() {
+        Ok(n) => (n * millis * 1000.0).round(),
+        Err(_) => usage(&format!("cannot parse {} {}", flag, t), options),
+    };
+    // beyond this the duration cannot be represented
+    if micros >= i64::MAX as f64 {
+        usage(&format!("{} {} is too long", flag, t), options);
+    }
+    Some(Duration::microseconds(micros as i64))
+}
+
+// parse a time zone option
+fn zone(options: &ArgMatches, name: &str, flag: &str) -> Option<Zone> {
+    let z = options.value_of(name)?;
+    match z.parse::<Zone>() {
+        Err(e) => usage(&format!("problem with {}: {}", flag, e), options),
+        Ok(z) => Some(z),
     }
 }
 
@@ -140,19 +556,34 @@ fn app<'a>() -> App<'a, 'a> {
     clap_app!(
         hun =>
         (version: crate_version!())
-        (author: crate_authors!("\n"))
+        (author: env!("CARGO_PKG_AUTHORS"))
         (about: crate_description!())
-        (@arg LOG: "The log file to search in")
+        (@arg LOG: "The log file to search in; end it with a quoted * to include its rotated logs")
         (@arg WHEN: ... "The period of time to search for events in")
-        (@arg format: -f --format [rx] +takes_value "The time stamp format")
+        (@arg when: -w --when [expr] +takes_value +multiple number_of_values(1) "Another period of time to search for events in; may be repeated")
+        (@arg when_file: --("when-file") [file] +takes_value "A file of periods of time to search for events in, one per line")
+        (@arg format: -f --format [fmt] +takes_value +multiple number_of_values(1) "The time stamp format, such as '%Y-%m-%d %T', or a regex prefixed with 'regex:'; may be repeated")
+        (@arg preset: -p --preset [name] +takes_value conflicts_with[format] "A named time stamp format, such as nginx or syslog")
+        (@arg detect: -d --detect conflicts_with[format preset] "Choose the time stamp format by sampling the log")
+        (@arg list_presets: --("list-presets") "List the named time stamp formats with a sample line for each")
         (@arg long_help: --("long-help") "Long help information explaining formats and time expressions")
         (@arg verbose: --("verbose") "Provide the precise time range and line numbers")
+        (@arg dir: --dir [dir] +takes_value conflicts_with[start end] "Search the logs in a directory instead of a single log")
+        (@arg label: -l --label "Prefix each line with the name of the log it comes from")
+        (@arg index: -i --index "Keep a sparse index of the log beside it to speed up later searches")
+        (@arg follow: --follow conflicts_with[start end] "Keep reading the log as it is written until the end of the period")
+        (@arg count: -c --count conflicts_with[follow label] "Print the number of lines in the period rather than the lines")
+        (@arg histogram: --histogram [width] +takes_value conflicts_with[follow count label] "Print the number of lines in each part of the period this long, such as 1m")
+        (@arg bar: --bar requires[histogram] "Draw a bar for each part of the period in the histogram")
         (@arg start: -s --("start-line") [n] +takes_value "The first line to search from")
         (@arg end: -e --("end-line") [n] +takes_value "The last line to search to")
+        (@arg tolerance: -t --tolerance [duration] +takes_value "How far out of order timestamps may be, such as 2s or 500ms")
+        (@arg log_zone: --("log-zone") [zone] +takes_value "The time zone of timestamps without an offset")
+        (@arg query_zone: --("query-zone") [zone] +takes_value "The time zone of the time expression; local by default")
     )
 }
 
-fn usage<'a>(msg: &str, matches: ArgMatches<'a>) {
+fn usage(msg: &str, matches: &ArgMatches) -> ! {
     println!("ERROR: {}\n\n{}", msg, matches.usage());
     process::exit(1)
 }
@@ -177,12 +608,14 @@ Examples
     2019-01-12 3:14:59 -- the way to dusty death
 
     > hun --verbose log.txt from 3 am today until 3:06
-    lines 12345 - 12349
+    searching for events in the range 2019-01-12 03:00:00 - 2019-01-12 03:06:00
     2019-01-12 3:00:01 -- tomorrow and tomorrow and tomorrow
     sometimes there's garbage between timestamps
     2019-01-12 3:05:13 -- creeps in this petty pace from day to day
-    2019-01-12 3:06:00 -- to the last syllable of recorded time
-    [exit stage left pursued by bear]
+    lines 12345 - 12347
+
+Lines are printed as they are read, so the line numbers of the range found are
+only reported once it has all been printed.
 
 Time Expressions
 ================
@@ -204,15 +637,87 @@ it doesn't hurt to be be specific. E.g.,
 Timestamp Formats
 =================
 
-A time stamp format must be a string that can compile to a regular expression
-with named capturing groups for "year", "month", "day", "minute", "hour", and
+The --format option takes a strftime-style format such as
+
+    %Y-%m-%d %H:%M:%S
+    %d/%b/%Y:%T %z
+
+The directives understood are
+
+    %Y       four-digit year
+    %y       two-digit year; 69 - 99 are 1969 - 1999, 00 - 68 are 2000 - 2068
+    %m       month number
+    %b, %h   abbreviated month name, such as "Jan"
+    %B       month name, such as "January"
+    %d, %e   day of the month, possibly padded with a space
+    %H, %k   hour on a 24-hour clock
+    %I, %l   hour on a 12-hour clock
+    %p       "AM", "pm", "a.m.", and the like
+    %M       minute
+    %S       second
+    %f       digits of a fraction of a second
+    %.f      optional fraction of a second with its "." or ","
+    %z, %:z  offset from UTC, such as "+0200" or "-05:00"
+    %Z       "Z", "UTC", "GMT", or an offset
+    %s       seconds since the Unix epoch
+    %a, %A   day of the week, which is ignored
+    %T       %H:%M:%S
+    %R       %H:%M
+    %F       %Y-%m-%d
+    %D       %m/%d/%y
+    %%       "%"
+
+A run of whitespace in the format matches any run of whitespace. Anything else
+matches itself.
+
+Alternatively, a format prefixed with "regex:" is a regular expression with
+named capturing groups for "year", "month", "day", "minute", "hour", and
 "second". No named captures are required, but a format without any captures
-won't do a very good job finding timestamps. Be aware that you cannot reuse a
-named capture name in Rust regexes. The expression
+won't do a very good job finding timestamps.
+
+    > hun --format 'regex:^(?P<year>\d{4})(?P<month>\d\d)(?P<day>\d\d)T(?P<hour>\d\d)(?P<minute>\d\d)(?P<second>\d\d)' log.txt today
+
+The "month" capture may be a number or an English month name, such as
+"January", or its abbreviation, such as "Jan" or "Sept". A format may also have
+an "ampm" capture, such as "AM", "pm", or "p.m.", in which case the "hour"
+capture is taken to be on a 12-hour clock.
+
+A format may leave out the year, as syslog does. For example,
+
+    %b %e %T
+
+The year of each timestamp is then taken to be the latest that does not put it
+after the time the log file was last modified or, if the log has no
+modification time, the year that puts it nearest the start of the time
+expression. A log that runs from December into January is thus still in order.
+
+A format may also have a "fraction" capture for the digits of a fraction of a
+second, such as the "123" in "03:00:01.123". Any number of digits is allowed,
+though only the first nine, nanoseconds, are significant.
+
+A format may also have an "offset" (or "tz") capture for the time zone offset
+of the timestamp, such as "Z", "UTC", "+02", "+0200", or "-05:00". Timestamps
+with an offset are converted into the time zone of the time expression before
+they are compared with it.
+
+Timestamps that count from the Unix epoch, 1970-01-01 00:00:00 UTC, can be
+captured instead with "epoch" for seconds, such as "1547262001" or
+"1547262001.123", or with "epoch_ms", "epoch_us", or "epoch_ns" for whole
+milliseconds, microseconds, or nanoseconds. For example,
+
+    ^(?P<epoch>\d{10}(?:\.\d+)?)\s
+
+Be aware that you cannot reuse a named capture name in Rust regexes. The
+expression
 
     (?<foo>f) (?<bar>b) | (?<bar>b) (?<foo>f)
 
-is ill-formed.
+is ill-formed. Instead, give --format once for each alternative. The formats
+are tried in the order given and a line's timestamp is the first one found.
+This also serves for a log that changes formats part way through, say after an
+upgrade, which can then be searched as a single timeline.
+
+    > hun -f '%b %e %T' -f '%F %T' service.log from 3 am today until 3:15
 
 The default format is
 
@@ -224,18 +729,221 @@ This is meant to match most log timestamps without matching quoted timestamps
 in logged SQL or a data serialization language such as JSON or XML.
 
 This is obviously quite a bit to type on the command line. Most often the
-default pattern will match a log file's timestamps.
+default pattern will match a log file's timestamps, and when it does not a
+strftime-style format is usually much shorter.
+
+Formats for many common kinds of log are built in and can be chosen by name
+with --preset. --list-presets shows each of them with a line it matches and
+any other name it goes by.
+
+    > hun --preset nginx access.log from 3 am today until 3:15
+
+If you do not know which to use, --detect will sample lines from the head,
+middle, and tail of the log and choose the preset that finds the most
+timestamps in increasing order. With --verbose the choice is reported.
+
+    > hun --detect --verbose mystery.log from 3 am today until 3:06
+    detected the syslog format: ^(?P<month>[A-Za-z]{3}) +(?P<day>\d{1,2}) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)
+    searching for events in the range 2019-01-12 03:00:00 - 2019-01-12 03:06:00
+    Jan 12 03:00:01 myhost sshd[1234]: Accepted publickey for root
+    lines 12345 - 12345
 
 If a line contains no timestamp it will be treated as having the same timestamp
 as closest line before it with a timestamp.
 
+Time Zones
+==========
+
+The time expression is understood to be in the local time zone unless the
+--query-zone option gives another. Timestamps without an offset are assumed to
+be in the same zone as the time expression unless the --log-zone option gives
+another. A zone is "local", "UTC", or an offset such as "+0200" or "-05:00".
+
+    > hun --log-zone UTC --query-zone -05:00 log.txt from 10 pm yesterday until 11 pm
+
+Compressed Logs
+===============
+
+A log compressed with gzip, such as a rotated log, can be searched directly.
+It is recognized by its first bytes rather than its name. The log is
+decompressed once to find where its lines begin, and places from which
+decompression can resume are noted along the way, so the search itself costs
+little more than it would for the uncompressed log. These places are not kept
+between searches, so each search of the log decompresses it once.
+
+    > hun app.log.2.gz from 3 am yesterday until 3:15
+
+Logs compressed with zstd or xz are recognized the same way. A zstd log in the
+seekable format, which is a series of independently compressed frames with a
+table of their sizes at the end, is searched in place like a gzipped log. A
+plain zstd stream or an xz log has no places from which decompression can
+resume, so it is first decompressed into a temporary file. This costs a pass
+over the whole log and disk space for its uncompressed text.
+
+    > hun app.log.3.zst from 3 am yesterday until 3:15
+
+Rotated Logs
+============
+
+A log ending in a quoted "*" stands for the log together with its rotated
+predecessors: every file in its directory whose name begins with the rest.
+These are taken as one continuous timeline, ordered by their first
+timestamps, and only those that span part of the time expression are
+searched, so a period that crosses a rotation is found whole.
+
+    > hun 'app.log*' from 11:50 pm yesterday until 12:10 am
+
+Rotated logs may be compressed. Logs without any timestamps, such as one
+rotated moments ago, are passed over. The --start-line and --end-line options
+cannot be used with rotated logs or --dir. With --verbose the lines found are
+reported for each log separately.
+
+Directories
+===========
+
+Logs kept as one file per hour or per day can be searched together with the
+--dir option, which takes the place of the log argument.
+
+    > hun --dir /var/log/app from 3 am yesterday until 3:15
+
+Every file in the directory, other than hidden files and indexes, is taken to
+be part of one timeline, as with rotated logs. The first and last timestamps of
+an uncompressed log are found by reading only its head and tail, so logs
+outside the time expression cost little. The lines found are printed a log at
+a time, in the order of the logs' first timestamps, so the logs should not
+overlap in time: the lines of logs that do are not interleaved.
+
+With several logs, the --label option prefixes each line with the name of the
+log it comes from.
+
+    > hun --label --dir /var/log/app from 3 am yesterday until 3:15
+    /var/log/app/03.log: 2019-01-12 3:00:01 -- tomorrow and tomorrow and tomorrow
+
+Indexes
+=======
+
+Before it can search a log trufflehunter reads all of it to find where its
+lines begin. For a log of many gigabytes searched again and again, the --index
+option saves most of this work by keeping a sparse index of the log in a file
+beside it, such as app.log.hunidx for app.log.
+
+    > hun --index /var/log/app.log from 3 am today until 3:15
+
+The index records where the lines begin and the timestamp found about every
+megabyte of the log, so later searches go straight to the right part of it.
+It is created by the first search with --index and brought up to date by
+reading only what has been added when the log grows. If the log has been
+rewritten or truncated it is indexed afresh. If the index cannot be written,
+the log is still searched. Compressed logs are searched without an index, and
+--index cannot be used with rotated logs or --dir.
+
+Following a Log
+===============
+
+When the time expression ends in the future, the --follow option keeps reading
+the log as it is written, as tail -f does, until it finds a line timestamped
+at or after the end of the period.
+
+    > hun --follow app.log from 5 minutes ago until 10 minutes from now
+
+A period that begins after the last line of the log is simply waited for. If
+the log is rotated, by renaming it and creating another in its place or by
+truncating it, the new log is followed from its beginning. A line is only
+printed once it is complete. With --verbose the lines found are reported for
+each log followed. The --follow option cannot be used with compressed logs,
+rotated logs, --dir, or line bounds.
+
+Several Periods
+===============
+
+To search one log for several periods, such as the minutes around each of a
+day's deploys, give each period after --when, or put them in a file, one per
+line, and give it to --when-file. Blank lines and lines beginning with # are
+ignored. Any time expression given after the log is searched for first.
+
+    > hun log.txt --when 'from 3 am today until 3:15' --when 'from 9:30 am until 9:45'
+    > hun log.txt --when-file deploys.txt
+
+A single log is opened once, and the timestamps found while looking for the
+start of each period are kept, so the search for the next period begins close
+to its start. With --index, rotated logs, or --dir, the logs are searched
+afresh for each period. The periods are searched in the order given, and lines
+in periods that overlap are printed once for each. A period before or after the whole log is
+passed over. With --verbose each period is reported along with the lines found
+in it. The --follow option cannot be used with several periods.
+
+Counting Lines
+==============
+
+To learn only how many lines fall in a period, give the --count option. The
+end of the period is found by bisection just as its start is, so the lines in
+it are never read and a long period costs no more to count than a short one.
+
+    > hun --count log.txt from 3 am today until 3:15
+    1234
+
+With --verbose the lines counted are reported together with the bytes they
+span. With several periods a count is printed for each. Lines out of the
+period that are interleaved with those in it, as with a tolerance, are counted
+too. The --count option cannot be used with rotated logs, --dir, or --follow.
+
+Histograms
+==========
+
+To see how busy a log was over a period, give the --histogram option the width
+of the buckets to divide the period into, as a number of days, hours, minutes,
+seconds, or milliseconds. The number of lines in each bucket is printed after
+the time it begins, and with --bar a bar is drawn beside it.
+
+    > hun --histogram 30m --bar log.txt from 2 am today to 4 am
+    2019-01-12 02:00:00  1041  ###########################
+    2019-01-12 02:30:00  1987  ##################################################
+    2019-01-12 03:00:00   312  ########
+    2019-01-12 03:30:00     0
+
+Where there are many lines to a bucket, the boundaries between buckets are
+found by bisection and the lines are counted as --count counts them, without
+being read. Otherwise the lines in the period are read once and each is
+counted in the bucket of its timestamp, or of the line before it if it has
+none. With several periods a histogram is printed for each. A period may be
+divided into at most 100,000 buckets. The --histogram option cannot be used
+with rotated logs, --dir, --count, or --follow.
+
+Out of Order Timestamps
+=======================
+
+Logs written by several threads often have lines a few milliseconds or seconds
+out of order. Ordinarily trufflehunter stops when it finds a timestamp earlier
+than one before it. The --tolerance option takes the longest such inversion to
+accept, as a number of hours, minutes, seconds, or milliseconds.
+
+    > hun --tolerance 2s log.txt from 3 am today until 3:15
+    > hun --tolerance 250ms log.txt from 3 am today until 3:15
+
+The search then begins looking that long before the start of the time
+expression and stops looking that long after its end, so every line in the
+range is found. Lines just outside the range that are interleaved with those
+within it are left out.
+
 Start and End Lines
 ===================
 
+Should trufflehunter come upon timestamps out of order while looking for the
+start of the time expression, it narrows them down to a region bounded by
+lines in order and searches that region line by line. With --verbose the
+region is reported.
+
+    > hun --verbose log.txt from 3 am today until 3:06
+    searching for events in the range 2019-01-12 03:00:00 - 2019-01-12 03:06:00
+    2019-01-12 3:00:01 -- tomorrow and tomorrow and tomorrow
+    lines 12338 - 12346 are misordered; they were searched line by line
+    lines 12345 - 12345
+
 The --start-line and --end-line options allow one to work around misordered log
 lines or failures of the time format. If trufflehunter errors out because it
-encounters misordered, or "misordered", lines, you may be able to work around
-these rough patches by specifying a sub-range of lines to search in.
+encounters misordered, or "misordered", lines, such as a first timestamp later
+than the last, you may be able to work around these rough patches by
+specifying a sub-range of lines to search in.
 
 The Name
 ========
@@ -0,0 +1,164 @@
+use crate::source::LineSource;
+use crate::time::Parser;
+use crate::{Extent, Lines, Problem, Search};
+use chrono::{Duration, NaiveDateTime};
+use std::collections::BTreeMap;
+
+/// A log opened once to be searched for several periods, such as the minutes
+/// around each of a day's deploys.
+///
+/// The timestamps of the lines probed while looking for the start of each
+/// period are kept, so the search for a later period begins between the
+/// nearest lines already known to be before and after its start rather than
+/// bisecting the whole log again. Should timestamps be found out of order,
+/// every later search covers the whole log, as a first one does.
+///
+/// # Examples
+/// ```no_run
+/// # extern crate chrono;
+/// # extern crate trufflehunter;
+/// # use chrono::NaiveDate;
+/// use trufflehunter::{Periods, Search, Source};
+///
+/// let at = |h, m| NaiveDate::from_ymd_opt(2019, 1, 12).unwrap().and_hms_opt(h, m, 0).unwrap();
+/// let mut periods = Periods::new(Source::open("production.log").unwrap());
+/// let search = Search::new(at(3, 0), at(3, 15));
+/// for (start, end) in vec![(at(3, 0), at(3, 15)), (at(9, 30), at(9, 45))] {
+///     for line in periods.iter(&search.clone().period(start, end)).unwrap() {
+///         print!("{}", String::from_utf8_lossy(&line.unwrap()));
+///     }
+/// }
+/// ```
+pub struct Periods<S> {
+    source: S,
+    // the timestamps of the lines probed so far, by line index
+    probes: BTreeMap<usize, NaiveDateTime>,
+    // a description of how the timestamps were parsed, as a search with
+    // other formats or zones would find other times
+    probed_with: String,
+    // whether every search so far has found timestamps in order
+    ordered: bool,
+}
+
+impl<S: LineSource> Periods<S> {
+    /// Prepares to search the source for several periods.
+    pub fn new(source: S) -> Periods<S> {
+        Periods {
+            source,
+            probes: BTreeMap::new(),
+            probed_with: String::new(),
+            ordered: true,
+        }
+    }
+    /// Runs the search over the source as [`Search::iter`] does, beginning
+    /// between the lines probed by earlier searches on either side of the
+    /// start of its period unless line bounds are given.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the range cannot be found, or
+    /// the log cannot be read.
+    pub fn iter(&mut self, search: &Search) -> Result<Lines<&mut S>, Problem> {
+        let (parser, first, last) = self.prepare(search)?;
+        let mut probes = vec![];
+        let found = search.iter_probed(&mut self.source, parser, first, last, &mut probes);
+        let misordered = found.as_ref().map(|lines| !lines.misordered.is_empty());
+        note(&mut self.probes, &mut self.ordered, probes, misordered);
+        found
+    }
+    /// Counts the lines in the range as [`Search::count`] does, beginning
+    /// between the lines probed by earlier searches as [`iter`](Periods::iter)
+    /// does.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the range cannot be found, or
+    /// the log cannot be read.
+    pub fn count(&mut self, search: &Search) -> Result<Extent, Problem> {
+        let (parser, first, last) = self.prepare(search)?;
+        let mut probes = vec![];
+        let found = search.count_probed(&mut self.source, &parser, first, last, &mut probes);
+        let misordered = found.as_ref().map(|extent| !extent.misordered.is_empty());
+        note(&mut self.probes, &mut self.ordered, probes, misordered);
+        found
+    }
+    /// Makes a histogram of the range as [`Search::histogram`] does,
+    /// beginning between the lines probed by earlier searches as
+    /// [`iter`](Periods::iter) does.
+    ///
+    /// # Errors
+    /// A [`Problem`] if the line bounds are bad, the width would divide the
+    /// range into more than [`MAX_BUCKETS`](crate::MAX_BUCKETS) buckets, the
+    /// range cannot be found, or the log cannot be read.
+    ///
+    /// # Panics
+    /// If the width is not positive.
+    pub fn histogram(
+        &mut self,
+        search: &Search,
+        width: Duration,
+    ) -> Result<Vec<(NaiveDateTime, usize)>, Problem> {
+        let (parser, first, last) = self.prepare(search)?;
+        let mut probes = vec![];
+        let found =
+            search.histogram_probed(&mut self.source, parser, width, first, last, &mut probes);
+        let misordered = found.as_ref().map(|(_, regions)| !regions.is_empty());
+        note(&mut self.probes, &mut self.ordered, probes, misordered);
+        found.map(|(buckets, _)| buckets)
+    }
+    /// Returns the source searched.
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+    // the parser for a search and the line bounds between which to look for
+    // the start of its range
+    fn prepare(
+        &mut self,
+        search: &Search,
+    ) -> Result<(Parser, Option<usize>, Option<usize>), Problem> {
+        search.check_bounds(&self.source)?;
+        let parser = search.parser(&self.source);
+        let described = format!("{:?}", parser);
+        if described != self.probed_with {
+            self.probes.clear();
+            self.probed_with = described;
+        }
+        let (mut first, mut last) = (search.start_line, search.end_line);
+        if self.ordered && first.is_none() && last.is_none() {
+            let (before, after) = self.bracket(search.widened().0);
+            first = before.map(|i| i + 1);
+            last = after.map(|i| i + 1);
+        }
+        Ok((parser, first, last))
+    }
+    // the last line probed that is timestamped before t and the first
+    // timestamped at or after it
+    fn bracket(&self, t: NaiveDateTime) -> (Option<usize>, Option<usize>) {
+        let after = self.probes.iter().find(|&(_, &u)| u >= t).map(|(&i, _)| i);
+        let before = self
+            .probes
+            .range(..after.unwrap_or(usize::MAX))
+            .rev()
+            .find(|&(_, &u)| u < t)
+            .map(|(&i, _)| i);
+        (before, after)
+    }
+}
+
+// keep the lines probed by a search unless it found timestamps out of order,
+// in which case nothing probed can be relied on to narrow later searches
+fn note(
+    kept: &mut BTreeMap<usize, NaiveDateTime>,
+    ordered: &mut bool,
+    probes: Vec<(usize, NaiveDateTime)>,
+    misordered: Result<bool, &Problem>,
+) {
+    match misordered {
+        Ok(false) | Err(Problem::LogBefore { .. }) | Err(Problem::LogAfter { .. }) => {
+            kept.extend(probes)
+        }
+        Ok(true) | Err(Problem::MisorderedTimestamps { .. }) => {
+            kept.clear();
+            *ordered = false;
+        }
+        Err(_) => {}
+    }
+}
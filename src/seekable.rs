@@ -0,0 +1,181 @@
+use crate::source::{index_lines, invalid, LineSource};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use zstd::stream::read::Decoder;
+
+// the magic number at the very end of a seekable zstd file
+const SEEKABLE_MAGIC: u32 = 0x8F92_EAB1;
+// the magic number of the skippable frame holding the seek table
+const SKIPPABLE_MAGIC: u32 = 0x184D_2A5E;
+// the length of the seek table's footer
+const FOOTER_SIZE: u64 = 9;
+
+/// A [`LineSource`] over a log compressed in the [seekable zstd
+/// format](https://github.com/facebook/zstd/blob/dev/contrib/seekable_format/zstd_seekable_compression_format.md).
+///
+/// A seekable zstd file is a series of independent zstd frames followed by a
+/// table of their sizes, so any part of the log can be read by decompressing
+/// only the frame that holds it. Constructing a `ZstdSource` decompresses the
+/// log once to find where its lines begin.
+///
+/// [`open_log`](crate::open_log) uses a `ZstdSource` for seekable zstd files
+/// and decompresses other zstd files into a temporary file.
+pub struct ZstdSource<R> {
+    reader: R,
+    frames: Vec<Frame>,
+    offsets: Vec<u64>,
+    // the index of the frame last decompressed and its text
+    cached: Option<(usize, Vec<u8>)>,
+    path: Option<PathBuf>,
+}
+
+// the position of a frame in the compressed and decompressed data
+struct Frame {
+    input: u64,
+    input_size: u64,
+    output: u64,
+    output_size: u64,
+}
+
+impl<R: Read + Seek> ZstdSource<R> {
+    /// Constructs a `ZstdSource`, reading the seek table of `reader` and
+    /// decompressing all of it to index it.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while reading the data. Data that is not
+    /// in the seekable zstd format, or that is corrupt, produces an error of
+    /// kind `InvalidData`.
+    pub fn new(mut reader: R) -> io::Result<ZstdSource<R>> {
+        let frames = seek_table(&mut reader)?.ok_or_else(|| invalid("not a seekable zstd file"))?;
+        reader.seek(SeekFrom::Start(0))?;
+        // the decoder skips the skippable frame holding the seek table
+        let mut decoder = Decoder::new(reader)?;
+        let offsets = index_lines(&mut decoder)?;
+        let reader = decoder.finish().into_inner();
+        let length = frames.last().map_or(0, |f| f.output + f.output_size);
+        if offsets.last() != Some(&length) {
+            return Err(invalid("the zstd seek table does not match the data"));
+        }
+        Ok(ZstdSource {
+            reader,
+            frames,
+            offsets,
+            cached: None,
+            path: None,
+        })
+    }
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+    // the decompressed text of frame i
+    fn frame(&mut self, i: usize) -> io::Result<&[u8]> {
+        if self.cached.as_ref().is_none_or(|(j, _)| *j != i) {
+            let frame = &self.frames[i];
+            let mut data = vec![0; frame.input_size as usize];
+            self.reader.seek(SeekFrom::Start(frame.input))?;
+            self.reader.read_exact(&mut data)?;
+            let text = zstd::bulk::decompress(&data, frame.output_size as usize)?;
+            if text.len() as u64 != frame.output_size {
+                return Err(invalid("the zstd seek table does not match the data"));
+            }
+            self.cached = Some((i, text));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+impl ZstdSource<File> {
+    /// Opens and indexes the seekable zstd file at `path`.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while opening or reading the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ZstdSource<File>> {
+        let mut source = ZstdSource::new(File::open(path.as_ref())?)?;
+        source.path = Some(path.as_ref().to_path_buf());
+        Ok(source)
+    }
+}
+
+impl<R: Read + Seek> LineSource for ZstdSource<R> {
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+    fn offset(&self, i: usize) -> u64 {
+        self.offsets[i]
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        // the last frame that begins at or before the offset
+        let i = self.frames.partition_point(|f| f.output <= offset);
+        if i == 0 {
+            return Ok(0);
+        }
+        let start = self.frames[i - 1].output;
+        let text = self.frame(i - 1)?;
+        let text = text.get((offset - start) as usize..).unwrap_or_default();
+        let n = text.len().min(buf.len());
+        buf[..n].copy_from_slice(&text[..n]);
+        Ok(n)
+    }
+    fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+// whether the reader ends with a seek table
+pub(crate) fn is_seekable<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    Ok(seek_table(reader)?.is_some())
+}
+
+// the frames listed in the seek table at the end of the reader, or None if it
+// does not have one
+fn seek_table<R: Read + Seek>(reader: &mut R) -> io::Result<Option<Vec<Frame>>> {
+    let length = reader.seek(SeekFrom::End(0))?;
+    if length < FOOTER_SIZE + 8 {
+        return Ok(None);
+    }
+    let mut footer = [0; FOOTER_SIZE as usize];
+    reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+    reader.read_exact(&mut footer)?;
+    if u32_at(&footer, 5) != SEEKABLE_MAGIC || footer[4] & 0x7c != 0 {
+        return Ok(None);
+    }
+    let count = u32_at(&footer, 0) as u64;
+    let entry_size = if footer[4] & 0x80 != 0 { 12 } else { 8 };
+    let table_size = count * entry_size + FOOTER_SIZE;
+    if length < table_size + 8 {
+        return Ok(None);
+    }
+    let mut table = vec![0; (table_size + 8) as usize];
+    reader.seek(SeekFrom::Start(length - table_size - 8))?;
+    reader.read_exact(&mut table)?;
+    if u32_at(&table, 0) != SKIPPABLE_MAGIC || u32_at(&table, 4) as u64 != table_size {
+        return Ok(None);
+    }
+    let mut frames = Vec::with_capacity(count as usize);
+    let (mut input, mut output) = (0, 0);
+    for entry in table[8..]
+        .chunks_exact(entry_size as usize)
+        .take(count as usize)
+    {
+        let input_size = u32_at(entry, 0) as u64;
+        let output_size = u32_at(entry, 4) as u64;
+        frames.push(Frame {
+            input,
+            input_size,
+            output,
+            output_size,
+        });
+        input += input_size;
+        output += output_size;
+    }
+    if input + table_size + 8 != length {
+        return Ok(None);
+    }
+    Ok(Some(frames))
+}
+
+fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
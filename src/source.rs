@@ -0,0 +1,434 @@
+use crate::gzip::GzipSource;
+use crate::index::{IndexedSource, INDEX_SUFFIX};
+use crate::seekable::{is_seekable, ZstdSource};
+use larry::Larry;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Random access to the lines of a log.
+///
+/// A `LineSource` knows where each of its lines begins and can read bytes
+/// from any offset. This is all a [`Search`](crate::Search) needs to bisect a
+/// log and then stream the lines it finds.
+pub trait LineSource {
+    /// Returns the number of lines.
+    fn len(&self) -> usize;
+    /// Returns whether there are no lines at all.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the byte offset at which line `i` begins. `offset(len())` is
+    /// the length of the source in bytes.
+    ///
+    /// A source that must read to find an offset, and fails to, may return any
+    /// offset so long as the next call to [`read_at`](LineSource::read_at)
+    /// returns the error.
+    fn offset(&self, i: usize) -> u64;
+    /// Reads bytes beginning at `offset` into `buf`, returning the number of
+    /// bytes read. A return value of 0 means the end of the source has been
+    /// reached.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+    /// Returns the path of the file the lines come from, if there is one.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+    /// Returns the bytes of line `i`, including its line terminator.
+    fn line(&mut self, i: usize) -> io::Result<Vec<u8>> {
+        if i >= self.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("index {} in source of only {} lines", i, self.len()),
+            ));
+        }
+        let start = self.offset(i);
+        let mut buf = vec![0; self.offset(i + 1).saturating_sub(start) as usize];
+        if buf.is_empty() {
+            // only a source that could not find the offsets has an empty line,
+            // and reading is how it says so
+            self.read_at(start, &mut buf)?;
+        }
+        read_exact_at(self, start, &mut buf)?;
+        Ok(buf)
+    }
+}
+
+// fill buf from offset, failing if the source ends first
+pub(crate) fn read_exact_at<S: LineSource + ?Sized>(
+    source: &mut S,
+    mut offset: u64,
+    mut buf: &mut [u8],
+) -> io::Result<()> {
+    while !buf.is_empty() {
+        match source.read_at(offset, buf) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "source is shorter than its line index",
+                ))
+            }
+            Ok(n) => {
+                offset += n as u64;
+                buf = &mut buf[n..];
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// an error for data that is not as it should be, such as a corrupt index
+pub(crate) fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// the offsets at which the lines read from the reader begin, followed by the
+// number of bytes read
+pub(crate) fn index_lines<R: Read>(reader: &mut R) -> io::Result<Vec<u64>> {
+    let mut offsets = vec![];
+    let length = scan_lines(reader, |offset| offsets.push(offset))?;
+    offsets.push(length);
+    Ok(offsets)
+}
+
+// pass the offset at which each line read from the reader begins to f,
+// returning the number of bytes read
+pub(crate) fn scan_lines<R: Read, F: FnMut(u64)>(reader: &mut R, mut f: F) -> io::Result<u64> {
+    let mut buf = vec![0; 1 << 16];
+    let mut offset: u64 = 0;
+    let mut line_start: Option<u64> = None;
+    let mut last_was_cr = false;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for (j, &b) in buf[..n].iter().enumerate() {
+            let here = offset + j as u64;
+            if last_was_cr && b != b'\n' {
+                // a lone carriage return ended the preceding line
+                line_start = None;
+            }
+            if line_start.is_none() {
+                line_start = Some(here);
+                f(here);
+            }
+            last_was_cr = b == b'\r';
+            if b == b'\n' {
+                line_start = None;
+            }
+        }
+        offset += n as u64;
+    }
+    Ok(offset)
+}
+
+/// A [`LineSource`] over any seekable reader.
+///
+/// Constructing a `Source` scans the reader once to find where its lines
+/// begin. After that lines are only read as they are needed. A line ends
+/// with `\n`, `\r\n`, or a lone `\r`.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::{LineSource, Source};
+///
+/// let mut source = Source::from(&b"foo\nbar\n"[..]);
+/// assert_eq!(2, source.len());
+/// assert_eq!(b"bar\n", &source.line(1).unwrap()[..]);
+/// ```
+pub struct Source<R> {
+    reader: R,
+    offsets: Vec<u64>,
+    path: Option<PathBuf>,
+}
+
+impl<R: Read + Seek> Source<R> {
+    /// Constructs a `Source`, indexing the lines of `reader`.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while reading the data.
+    pub fn new(mut reader: R) -> io::Result<Source<R>> {
+        reader.seek(SeekFrom::Start(0))?;
+        let offsets = index_lines(&mut reader)?;
+        Ok(Source {
+            reader,
+            offsets,
+            path: None,
+        })
+    }
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl Source<File> {
+    /// Opens and indexes the file at `path`.
+    ///
+    /// # Errors
+    /// Any `std::io::Error` arising while opening or reading the file.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Source<File>> {
+        let mut source = Source::new(File::open(path.as_ref())?)?;
+        source.path = Some(path.as_ref().to_path_buf());
+        Ok(source)
+    }
+}
+
+impl From<Vec<u8>> for Source<Cursor<Vec<u8>>> {
+    fn from(bytes: Vec<u8>) -> Self {
+        in_memory(bytes)
+    }
+}
+
+impl<'a> From<&'a [u8]> for Source<Cursor<&'a [u8]>> {
+    fn from(bytes: &'a [u8]) -> Self {
+        in_memory(bytes)
+    }
+}
+
+// a source over bytes in memory, which unlike a file cannot fail to be read
+fn in_memory<T: AsRef<[u8]>>(bytes: T) -> Source<Cursor<T>> {
+    Source::new(Cursor::new(bytes)).unwrap()
+}
+
+impl<R: Read + Seek> LineSource for Source<R> {
+    fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+    fn offset(&self, i: usize) -> u64 {
+        self.offsets[i]
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read(buf)
+    }
+    fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl<S: LineSource + ?Sized> LineSource for Box<S> {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+    fn offset(&self, i: usize) -> u64 {
+        (**self).offset(i)
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+    fn path(&self) -> Option<&Path> {
+        (**self).path()
+    }
+    fn line(&mut self, i: usize) -> io::Result<Vec<u8>> {
+        (**self).line(i)
+    }
+}
+
+impl<S: LineSource + ?Sized> LineSource for &mut S {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+    fn offset(&self, i: usize) -> u64 {
+        (**self).offset(i)
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read_at(offset, buf)
+    }
+    fn path(&self) -> Option<&Path> {
+        (**self).path()
+    }
+    fn line(&mut self, i: usize) -> io::Result<Vec<u8>> {
+        (**self).line(i)
+    }
+}
+
+// the first bytes of files compressed by the codecs understood
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0];
+
+// the number of bytes read from each end of a log to find its span
+const ENDS_SIZE: u64 = 1 << 16;
+
+// the lines read from each end of a log, and the index of the first from the
+// tail
+type Ends = (Source<Cursor<Vec<u8>>>, usize);
+
+/// Opens and indexes the log at `path`, decompressing it if its first bytes
+/// show it to be compressed.
+///
+/// Gzip-compressed logs and logs in the seekable zstd format are read in
+/// place by a [`GzipSource`] or [`ZstdSource`]. Other zstd-compressed logs
+/// and xz-compressed logs have no points from which decompression can resume,
+/// so they are decompressed into a temporary file which is then searched
+/// like a plain log.
+///
+/// # Errors
+/// Any `std::io::Error` arising while opening or reading the file.
+pub fn open_log<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn LineSource>> {
+    let path = path.as_ref();
+    let mut magic = [0; 6];
+    let mut file = File::open(path)?;
+    let n = file.read(&mut magic)?;
+    let magic = &magic[..n];
+    if magic.starts_with(GZIP_MAGIC) {
+        Ok(Box::new(GzipSource::open(path)?))
+    } else if magic.starts_with(ZSTD_MAGIC) {
+        if is_seekable(&mut file)? {
+            Ok(Box::new(ZstdSource::open(path)?))
+        } else {
+            file.seek(SeekFrom::Start(0))?;
+            unpack(zstd::stream::read::Decoder::new(file)?, path)
+        }
+    } else if magic == XZ_MAGIC {
+        file.seek(SeekFrom::Start(0))?;
+        unpack(xz2::read::XzDecoder::new_multi_decoder(file), path)
+    } else {
+        Ok(Box::new(Source::open(path)?))
+    }
+}
+
+/// Opens a log as [`open_log`] does, but keeps a sparse index of an
+/// uncompressed log in a file beside it so that the whole log need not be
+/// indexed again each time it is opened. See [`IndexedSource`].
+///
+/// # Errors
+/// Any `std::io::Error` arising while opening or reading the file.
+pub fn open_indexed<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn LineSource>> {
+    let path = path.as_ref();
+    if is_compressed(path)? {
+        open_log(path)
+    } else {
+        Ok(Box::new(IndexedSource::open(path)?))
+    }
+}
+
+/// Returns the paths of a log and its rotated predecessors: the files in the
+/// log's directory whose names begin with the log's name, such as
+/// `app.log`, `app.log.1`, and `app.log.2.gz`. The log itself need not exist.
+///
+/// Indexes kept by [`IndexedSource`] are left out. The paths are sorted by
+/// name. [`Search::iter_all`](crate::Search::iter_all)
+/// puts them in order by time.
+///
+/// # Errors
+/// Any `std::io::Error` arising while reading the directory.
+pub fn rotated_logs<P: AsRef<Path>>(path: P) -> io::Result<Vec<PathBuf>> {
+    let path = path.as_ref();
+    let prefix = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return Ok(vec![]),
+    };
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && !name.ends_with(INDEX_SUFFIX) && entry.path().is_file() {
+            paths.push(path.with_file_name(entry.file_name()));
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns the paths of the files in a directory, sorted by name. Hidden
+/// files, whose names begin with ".", indexes kept by [`IndexedSource`], and
+/// subdirectories are left out.
+///
+/// # Errors
+/// Any `std::io::Error` arising while reading the directory.
+pub fn logs_in<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && !name.ends_with(INDEX_SUFFIX) && entry.path().is_file() {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+// the complete lines within the first and last few kilobytes of an
+// uncompressed log, which are enough to find its span without indexing all of
+// it; None if the log is compressed or small enough to be opened whole
+pub(crate) fn log_ends(path: &Path) -> io::Result<Option<Ends>> {
+    let mut file = File::open(path)?;
+    let length = file.metadata()?.len();
+    if length <= 2 * ENDS_SIZE {
+        return Ok(None);
+    }
+    let mut head = vec![0; ENDS_SIZE as usize];
+    file.read_exact(&mut head)?;
+    if compressed(&head) {
+        return Ok(None);
+    }
+    let mut tail = vec![0; ENDS_SIZE as usize];
+    file.seek(SeekFrom::Start(length - ENDS_SIZE))?;
+    file.read_exact(&mut tail)?;
+    // drop the partial lines where the ends were cut from the middle
+    if let Some(i) = head.iter().rposition(|&b| b == b'\n') {
+        head.truncate(i + 1);
+    }
+    if let Some(i) = tail.iter().position(|&b| b == b'\n') {
+        tail.drain(..=i);
+    }
+    let tail_start = head.iter().filter(|&&b| b == b'\n').count();
+    head.extend(tail);
+    let mut source = Source::from(head);
+    source.path = Some(path.to_path_buf());
+    Ok(Some((source, tail_start)))
+}
+
+// whether the log at the path is compressed
+pub(crate) fn is_compressed(path: &Path) -> io::Result<bool> {
+    let mut magic = [0; 6];
+    let n = File::open(path)?.read(&mut magic)?;
+    Ok(compressed(&magic[..n]))
+}
+
+// whether the first bytes of a log show it to be compressed
+fn compressed(head: &[u8]) -> bool {
+    [GZIP_MAGIC, ZSTD_MAGIC, XZ_MAGIC]
+        .iter()
+        .any(|magic| head.starts_with(magic))
+}
+
+// decompresses a log into a temporary file and indexes that
+fn unpack<R: Read>(mut decoder: R, path: &Path) -> io::Result<Box<dyn LineSource>> {
+    let mut file = tempfile::tempfile()?;
+    io::copy(&mut decoder, &mut file)?;
+    file.seek(SeekFrom::Start(0))?;
+    let mut source = Source::new(file)?;
+    // problems and modification times concern the compressed log
+    source.path = Some(path.to_path_buf());
+    Ok(Box::new(source))
+}
+
+impl LineSource for Larry {
+    fn len(&self) -> usize {
+        Larry::len(self)
+    }
+    fn offset(&self, i: usize) -> u64 {
+        if i < Larry::len(self) {
+            Larry::offset(self, i).unwrap()
+        } else {
+            // the larry does not expose its length, but it is that of the file
+            self.file.metadata().map(|m| m.len()).unwrap_or(0)
+        }
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read(buf)
+    }
+}
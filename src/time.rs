@@ -0,0 +1,372 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone,
+    Utc,
+};
+use regex::bytes::{Captures, Match, Regex};
+use std::fmt;
+use std::str::{self, FromStr};
+use std::time::SystemTime;
+
+/// A time zone in which timestamps or time expressions are understood.
+///
+/// A zone is either a fixed offset from UTC or the local time zone of the
+/// machine, which may change its offset with daylight saving time.
+///
+/// # Examples
+/// ```
+/// use trufflehunter::Zone;
+///
+/// assert!("local".parse::<Zone>().is_ok());
+/// assert_eq!("Z".parse::<Zone>(), "+00:00".parse::<Zone>());
+/// assert_eq!("-0500".parse::<Zone>(), "-05".parse::<Zone>());
+/// assert!("Mars/Olympus_Mons".parse::<Zone>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    /// The time zone of the machine.
+    Local,
+    /// A fixed offset from UTC.
+    Fixed(FixedOffset),
+}
+
+impl Zone {
+    /// UTC itself.
+    pub fn utc() -> Zone {
+        Zone::Fixed(Utc.fix())
+    }
+    /// Returns the current time in this zone.
+    pub fn now(&self) -> NaiveDateTime {
+        self.of_utc(Utc::now().naive_utc())
+    }
+    // the given moment in this zone
+    pub(crate) fn at(self, t: SystemTime) -> NaiveDateTime {
+        self.of_utc(DateTime::<Utc>::from(t).naive_utc())
+    }
+    // convert a time in this zone to UTC; None if the time does not exist here
+    fn to_utc(self, t: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Zone::Local => Local
+                .from_local_datetime(&t)
+                .earliest()
+                .map(|t| t.naive_utc()),
+            Zone::Fixed(offset) => offset
+                .from_local_datetime(&t)
+                .single()
+                .map(|t| t.naive_utc()),
+        }
+    }
+    // convert a time in UTC to this zone
+    fn of_utc(self, t: NaiveDateTime) -> NaiveDateTime {
+        match self {
+            Zone::Local => Local.from_utc_datetime(&t).naive_local(),
+            Zone::Fixed(offset) => offset.from_utc_datetime(&t).naive_local(),
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zone::Local => write!(f, "local"),
+            Zone::Fixed(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+impl FromStr for Zone {
+    type Err = String;
+    /// Parses `local`, `UTC`, `Z`, or an offset such as `+02`, `+0200`, or
+    /// `-05:00`.
+    fn from_str(s: &str) -> Result<Zone, String> {
+        if s.eq_ignore_ascii_case("local") {
+            Ok(Zone::Local)
+        } else if let Some(offset) = parse_offset(s) {
+            Ok(Zone::Fixed(offset))
+        } else {
+            Err(format!("cannot understand \"{}\" as a time zone", s))
+        }
+    }
+}
+
+// parse the text of an offset capture
+fn parse_offset(s: &str) -> Option<FixedOffset> {
+    let s = s.trim();
+    if ["z", "utc", "gmt"]
+        .iter()
+        .any(|z| s.eq_ignore_ascii_case(z))
+    {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match s.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = s[1..].replacen(':', "", 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (h, m) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (
+            digits[..2].parse::<i32>().ok()?,
+            digits[2..].parse::<i32>().ok()?,
+        ),
+        _ => return None,
+    };
+    if m >= 60 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+}
+
+// turns lines into the times they are timestamped with
+#[derive(Debug, Clone)]
+pub(crate) struct Parser {
+    // the formats to try, in order
+    pub(crate) formats: Vec<Regex>,
+    // the zone of timestamps without an offset, if it differs from the query's
+    pub(crate) log_zone: Option<Zone>,
+    // the zone that times are normalized into
+    pub(crate) query_zone: Zone,
+    // how to choose a year for timestamps that lack one
+    pub(crate) year: YearHint,
+}
+
+// what is known about the year of timestamps that lack one
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum YearHint {
+    // the log was last modified at this time, so its timestamps are earlier
+    Before(NaiveDateTime),
+    // the log is being searched for times around this one
+    Near(NaiveDateTime),
+}
+
+impl YearHint {
+    // the time the date and time would be in the most likely year
+    fn place<F>(self, at_year: F) -> Option<NaiveDateTime>
+    where
+        F: Fn(i32) -> Option<NaiveDateTime>,
+    {
+        match self {
+            YearHint::Before(t) => {
+                // allow for clocks and time zones that disagree a little; go
+                // back far enough to find a leap day
+                let limit = t + Duration::days(1);
+                (0..5)
+                    .filter_map(|i| at_year(t.year() - i))
+                    .find(|&candidate| candidate <= limit)
+            }
+            YearHint::Near(t) => (-1..2)
+                .filter_map(|i| at_year(t.year() + i))
+                .min_by_key(|&candidate| (candidate - t).abs()),
+        }
+    }
+}
+
+impl Parser {
+    // the time of the timestamp on the line, if any, in the query's zone,
+    // according to the first format that finds one
+    pub(crate) fn timestamp(&self, line: &[u8]) -> Option<NaiveDateTime> {
+        self.formats
+            .iter()
+            .find_map(|rx| self.timestamp_in_format(rx, line))
+    }
+    fn timestamp_in_format(&self, rx: &Regex, line: &[u8]) -> Option<NaiveDateTime> {
+        let captures = rx.captures(line)?;
+        if let Some(utc) = epoch_timestamp(&captures) {
+            return Some(self.query_zone.of_utc(utc?));
+        }
+        let nd = naive_timestamp(&captures, self.year)?;
+        let offset = captures.name("offset").or_else(|| captures.name("tz"));
+        if let Some(offset) = offset {
+            let offset = parse_offset(text(offset))?;
+            let dt: DateTime<FixedOffset> = offset.from_local_datetime(&nd).single()?;
+            Some(self.query_zone.of_utc(dt.naive_utc()))
+        } else {
+            match self.log_zone {
+                Some(zone) => Some(self.query_zone.of_utc(zone.to_utc(nd)?)),
+                None => Some(nd),
+            }
+        }
+    }
+}
+
+// the text of a capture; anything a timestamp needs is ASCII
+fn text(m: Match<'_>) -> &str {
+    str::from_utf8(m.as_bytes()).unwrap_or("")
+}
+
+// the UTC time given by an epoch capture, if there is one; the inner option
+// is None if the capture cannot be parsed
+fn epoch_timestamp(captures: &Captures) -> Option<Option<NaiveDateTime>> {
+    let dt = if let Some(epoch) = captures.name("epoch") {
+        let epoch = text(epoch);
+        let (seconds, fraction) = match epoch.find(['.', ',']) {
+            Some(i) => (&epoch[..i], &epoch[i + 1..]),
+            None => (epoch, ""),
+        };
+        seconds.parse::<i64>().ok().and_then(|s| {
+            let nano = if fraction.is_empty() {
+                Some(0)
+            } else {
+                nanoseconds(fraction)
+            };
+            nano.and_then(|n| DateTime::from_timestamp(s, n))
+        })
+    } else if let Some(ms) = captures.name("epoch_ms") {
+        text(ms)
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_millis)
+    } else if let Some(us) = captures.name("epoch_us") {
+        text(us)
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_micros)
+    } else if let Some(ns) = captures.name("epoch_ns") {
+        text(ns)
+            .parse::<i64>()
+            .ok()
+            .map(DateTime::from_timestamp_nanos)
+    } else {
+        return None;
+    };
+    Some(dt.map(|dt| dt.naive_utc()))
+}
+
+// the time given by the captures, ignoring any offset
+fn naive_timestamp(captures: &Captures, year: YearHint) -> Option<NaiveDateTime> {
+    let mut y = Some(0);
+    let m;
+    let d;
+    let mut h;
+    let mn;
+    let s;
+    if let Some(year) = captures.name("year") {
+        let year = text(year);
+        if let Ok(n) = year.parse::<i32>() {
+            y = Some(if year.len() == 2 {
+                // the POSIX convention for two-digit years
+                n + if n < 69 { 2000 } else { 1900 }
+            } else {
+                n
+            });
+        }
+    } else {
+        y = None;
+    }
+    if let Some(month) = captures.name("month") {
+        if let Some(month) = parse_month(text(month)) {
+            m = month;
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    }
+    if let Some(day) = captures.name("day") {
+        if let Ok(day) = text(day).parse::<u32>() {
+            d = day;
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    }
+    if let Some(hour) = captures.name("hour") {
+        if let Ok(hour) = text(hour).parse::<u32>() {
+            h = hour;
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    }
+    if let Some(ampm) = captures.name("ampm") {
+        h = twenty_four_hour(h, text(ampm))?;
+    }
+    if let Some(minute) = captures.name("minute") {
+        if let Ok(minute) = text(minute).parse::<u32>() {
+            mn = minute;
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    }
+    if let Some(second) = captures.name("second") {
+        if let Ok(second) = text(second).parse::<u32>() {
+            s = second;
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    }
+    let nano = match captures.name("fraction") {
+        Some(fraction) => nanoseconds(text(fraction))?,
+        None => 0,
+    };
+    let at_year = |y| NaiveDate::from_ymd_opt(y, m, d)?.and_hms_nano_opt(h, mn, s, nano);
+    match y {
+        Some(y) => at_year(y),
+        None => year.place(at_year),
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+// a month given as a number or as an English name or abbreviation of at
+// least three letters
+fn parse_month(s: &str) -> Option<u32> {
+    if let Ok(n) = s.parse::<u32>() {
+        return Some(n);
+    }
+    let s = s.trim_end_matches('.').to_ascii_lowercase();
+    if s.len() < 3 {
+        return None;
+    }
+    MONTHS
+        .iter()
+        .position(|m| m.starts_with(&s))
+        .map(|i| i as u32 + 1)
+}
+
+// convert an hour on a 12-hour clock to one on a 24-hour clock given the
+// text of an ampm capture, such as "AM", "pm", or "p.m."
+fn twenty_four_hour(h: u32, ampm: &str) -> Option<u32> {
+    if h == 0 || h > 12 {
+        return None;
+    }
+    let ampm = ampm.replace('.', "").to_ascii_lowercase();
+    match ampm.as_str() {
+        "am" | "a" => Some(h % 12),
+        "pm" | "p" => Some(h % 12 + 12),
+        _ => None,
+    }
+}
+
+// the digits of a fraction of a second as nanoseconds; digits beyond the
+// ninth are ignored
+fn nanoseconds(digits: &str) -> Option<u32> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits = &digits[..digits.len().min(9)];
+    let n = digits.parse::<u32>().ok()?;
+    Some(n * 10u32.pow(9 - digits.len() as u32))
+}
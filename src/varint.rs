@@ -0,0 +1,28 @@
+use crate::source::invalid;
+use std::io::{self, Read, Write};
+
+// write a number as a LEB128 varint
+pub(crate) fn write_number<W: Write>(out: &mut W, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+// read a number written by write_number
+pub(crate) fn read_number<I: Read>(index: &mut I) -> io::Result<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        index.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(invalid("malformed index"))
+}
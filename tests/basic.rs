@@ -1,7 +1,8 @@
 // some sanity tests
 extern crate trufflehunter;
 use std::fs;
-use trufflehunter::{fetch_lines, Problem, DEFAULT_FORMAT};
+use std::io::Cursor;
+use trufflehunter::{fetch_lines, Problem, Search, Source, DEFAULT_FORMAT};
 extern crate chrono;
 use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
 extern crate regex;
@@ -28,7 +29,9 @@ fn date(s: &str) -> NaiveDateTime {
     let hour = caps["hour"].parse::<u32>().unwrap();
     let minute = caps["minute"].parse::<u32>().unwrap();
     let second = caps["second"].parse::<u32>().unwrap();
-    NaiveDate::from_ymd(year, month, day).and_hms(hour, minute, second)
+    NaiveDate::from_ymd_opt(year, month, day)
+        .and_then(|d| d.and_hms_opt(hour, minute, second))
+        .unwrap()
 }
 
 #[test]
@@ -51,16 +54,9 @@ fn simple_log() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 3:00:00"),
-        date("2000-1-3 3:00:01"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
-        Err(e) => assert!(false, format!("error: {:?}", e)),
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 3:00:00"), date("2000-1-3 3:00:01")).run(larry) {
+        Err(e) => panic!("error: {:?}", e),
         Ok((offset, lines)) => {
             assert_eq!(6, offset);
             assert!(lines[0].contains("what we're looking for"));
@@ -112,16 +108,9 @@ more garbage
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 3:00:00"),
-        date("2000-1-3 3:00:01"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
-        Err(e) => assert!(false, format!("error: {:?}", e)),
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 3:00:00"), date("2000-1-3 3:00:01")).run(larry) {
+        Err(e) => panic!("error: {:?}", e),
         Ok((offset, lines)) => {
             assert_eq!(16, offset);
             assert_eq!(3, lines.len());
@@ -189,16 +178,9 @@ more garbage
     let tests = extract_tests(text);
     assert_eq!(11, tests.len());
     for (date, o, n) in tests {
-        let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-        match fetch_lines(
-            larry,
-            date,
-            date.with_second(59).unwrap(),
-            None,
-            None,
-            DATE.clone(),
-        ) {
-            Err(e) => assert!(false, format!("error: {:?}", e)),
+        let larry = Larry::new(Path::new(name)).expect("could not make larry");
+        match Search::new(date, date.with_second(59).unwrap()).run(larry) {
+            Err(e) => panic!("error: {:?}", e),
             Ok((offset, lines)) => {
                 assert_eq!(o, offset);
                 assert_eq!(n, lines.len());
@@ -222,9 +204,9 @@ fn big_time_test(name: &str, text: &str, count: usize) {
         } else {
             end = d1 + Duration::seconds(2);
         }
-        let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-        match fetch_lines(larry, d1, end, None, None, DATE.clone()) {
-            Err(e) => assert!(false, format!("error: {:?}", e)),
+        let larry = Larry::new(Path::new(name)).expect("could not make larry");
+        match Search::new(d1, end).run(larry) {
+            Err(e) => panic!("error: {:?}", e),
             Ok((offset, lines)) => {
                 assert_eq!(o, offset);
                 assert_eq!(n, lines.len());
@@ -316,16 +298,12 @@ fn start_line_works() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 2:00:00"),
-        date("2000-1-3 2:30:00"),
-        Some(3),
-        None,
-        DATE.clone(),
-    ) {
-        Err(e) => assert!(false, format!("error: {:?}", e)),
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 2:30:00"))
+        .start_line(3)
+        .run(larry)
+    {
+        Err(e) => panic!("error: {:?}", e),
         Ok((_, lines)) => {
             assert_eq!(1, lines.len());
             assert!(lines[0].contains("what we're looking for"));
@@ -346,16 +324,12 @@ fn end_line_works() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 2:00:00"),
-        date("2000-1-3 2:30:00"),
-        None,
-        Some(2),
-        DATE.clone(),
-    ) {
-        Err(e) => assert!(false, format!("error: {:?}", e)),
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 2:30:00"))
+        .end_line(2)
+        .run(larry)
+    {
+        Err(e) => panic!("error: {:?}", e),
         Ok((_, lines)) => {
             assert_eq!(1, lines.len());
             assert!(lines[0].contains("what we're looking for"));
@@ -376,23 +350,16 @@ fn before_log() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 1:00:00"),
-        date("2000-1-3 1:30:00"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 1:00:00"), date("2000-1-3 1:30:00")).run(larry) {
         Err(e) => match e {
-            Problem::LogAfter => assert!(true),
+            Problem::LogAfter { .. } => (),
             _ => {
                 println!("{:?}", e);
-                assert!(false, "wrong error")
+                panic!("wrong error")
             }
         },
-        Ok(_) => assert!(false, "this was supposed to throw an error"),
+        Ok(_) => panic!("this was supposed to throw an error"),
     }
     fs::remove_file(name).expect("could not delete file");
 }
@@ -409,23 +376,16 @@ fn after_log() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 4:00:00"),
-        date("2000-1-3 4:30:00"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 4:00:00"), date("2000-1-3 4:30:00")).run(larry) {
         Err(e) => match e {
-            Problem::LogBefore => assert!(true),
+            Problem::LogBefore { .. } => (),
             _ => {
                 println!("{:?}", e);
-                assert!(false, "wrong error")
+                panic!("wrong error")
             }
         },
-        Ok(_) => assert!(false, "this was supposed to throw an error"),
+        Ok(_) => panic!("this was supposed to throw an error"),
     }
     fs::remove_file(name).expect("could not delete file");
 }
@@ -441,25 +401,18 @@ fn misordered() {
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 1:00:00"),
-        date("2000-1-3 3:00:00"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 1:00:00"), date("2000-1-3 3:00:00")).run(larry) {
         Err(e) => match e {
-            Problem::MisorderedTimestamps(..) => assert!(true),
+            Problem::MisorderedTimestamps { .. } => (),
             _ => {
                 println!("{:?}", e);
-                assert!(false, "wrong error")
+                panic!("wrong error")
             }
         },
         Ok((offset, lines)) => {
             println!("offset: {}\nlines: {:?}", offset, lines);
-            assert!(false, "this was supposed to throw an error")
+            panic!("this was supposed to throw an error")
         }
     }
     fs::remove_file(name).expect("could not delete file");
@@ -476,26 +429,421 @@ also not a timestamp
 "#,
     )
     .expect("could not write file");
-    let larry = Larry::new(&Path::new(name)).expect("could not make larry");
-    match fetch_lines(
-        larry,
-        date("2000-1-3 1:00:00"),
-        date("2000-1-3 3:00:00"),
-        None,
-        None,
-        DATE.clone(),
-    ) {
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match Search::new(date("2000-1-3 1:00:00"), date("2000-1-3 3:00:00")).run(larry) {
         Err(e) => match e {
-            Problem::NoTimestamps => assert!(true),
+            Problem::NoTimestamps { .. } => (),
             _ => {
                 println!("{:?}", e);
-                assert!(false, "wrong error")
+                panic!("wrong error")
             }
         },
         Ok((offset, lines)) => {
             println!("offset: {}\nlines: {:?}", offset, lines);
-            assert!(false, "this was supposed to throw an error")
+            panic!("this was supposed to throw an error")
+        }
+    }
+    fs::remove_file(name).expect("could not delete file");
+}
+
+#[test]
+fn streaming() {
+    let name = "streaming.log";
+    fs::write(
+        name,
+        r#"
+2000-1-3 1:00:00 not it
+2000-1-3 2:00:00 what we're looking for
+we should also get this line
+2000-1-3 2:30:00 and this line
+2000-1-3 3:00:00 not it
+"#,
+    )
+    .expect("could not write file");
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    let lines = Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 3:00:00"))
+        .iter(larry)
+        .expect("could not find range");
+    assert_eq!(2, lines.offset());
+    let lines = lines
+        .collect::<Result<Vec<_>, _>>()
+        .expect("could not read lines");
+    assert_eq!(3, lines.len());
+    assert_eq!(
+        &b"2000-1-3 2:00:00 what we're looking for\n"[..],
+        &lines[0][..]
+    );
+    assert_eq!(&b"2000-1-3 2:30:00 and this line\n"[..], &lines[2][..]);
+    fs::remove_file(name).expect("could not delete file");
+}
+
+#[test]
+fn fetch_lines_compatibility() {
+    let name = "fetch_lines_compatibility.log";
+    fs::write(
+        name,
+        r#"
+2000-1-3 2:00:00 not it
+2000-1-3 2:01:00 what we're looking for
+2000-1-3 3:00:00 not it
+"#,
+    )
+    .expect("could not write file");
+    let larry = Larry::new(Path::new(name)).expect("could not make larry");
+    match fetch_lines(
+        larry,
+        date("2000-1-3 2:00:00"),
+        date("2000-1-3 2:30:00"),
+        Some(3),
+        None,
+        DATE.clone(),
+    ) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            assert_eq!(1, lines.len());
+            assert!(lines[0].contains("what we're looking for"));
         }
     }
     fs::remove_file(name).expect("could not delete file");
 }
+
+#[test]
+fn in_memory_sources() {
+    let text = r#"
+2000-1-3 1:00:00 not it
+2000-1-3 2:00:00 what we're looking for
+we should also get this line
+2000-1-3 3:00:00 not it
+"#;
+    let search = Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 3:00:00"));
+    match search.run(Source::from(text.as_bytes().to_vec())) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            assert_eq!(2, lines.len());
+            assert!(lines[1].contains("we should also get this line"));
+        }
+    }
+    match search.run(Source::from(text.as_bytes())) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            assert_eq!(2, lines.len());
+        }
+    }
+    match search.run(Source::new(Cursor::new(text.replace('\n', "\r\n"))).unwrap()) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            assert_eq!("we should also get this line", lines[1]);
+        }
+    }
+}
+
+#[test]
+fn bad_line_bounds() {
+    let text = "2000-1-3 1:00:00 foo\n2000-1-3 2:00:00 bar\n";
+    let search = Search::new(date("2000-1-3 1:00:00"), date("2000-1-3 3:00:00"));
+    for search in [
+        search.clone().start_line(0),
+        search.clone().end_line(0),
+        search.clone().start_line(2).end_line(1),
+        search.clone().start_line(3),
+    ] {
+        match search.run(Source::from(text.as_bytes())) {
+            Err(Problem::BadLineBounds { .. }) => (),
+            Err(e) => panic!("wrong error: {:?}", e),
+            Ok(_) => panic!("this was supposed to throw an error"),
+        }
+    }
+}
+
+#[test]
+fn non_utf8_bytes() {
+    let first = &b"2000-1-3 1:00:00 foo\n"[..];
+    let middle = &b"2000-1-3 2:00:00 caf\xe9\n\xff\xfe\r\n2000-1-3 3:00:00 bar\n"[..];
+    let last = &b"\x80 2000-1-3 4:00:00 baz"[..];
+    let text = [first, middle, last].concat();
+    let search = Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 4:00:00"));
+    let lines = search
+        .iter(Source::from(&text[..]))
+        .expect("could not find range");
+    assert_eq!(1, lines.offset());
+    let bytes = lines
+        .collect::<Result<Vec<_>, _>>()
+        .expect("could not read lines")
+        .concat();
+    assert_eq!(middle, &bytes[..]);
+    let search = Search::new(date("2000-1-3 4:00:00"), date("2000-1-3 5:00:00"));
+    match search.run(Source::from(&text[..])) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(4, offset);
+            assert_eq!(vec!["\u{fffd} 2000-1-3 4:00:00 baz"], lines);
+        }
+    }
+}
+
+#[test]
+fn problems_name_their_files() {
+    let name = "problems_name_their_files.log";
+    fs::write(name, "not a timestamp\n").expect("could not write file");
+    let source = Source::open(name).expect("could not open file");
+    match Search::new(date("2000-1-3 1:00:00"), date("2000-1-3 3:00:00")).run(source) {
+        Err(e) => {
+            assert_eq!(Some(Path::new(name)), e.path());
+            assert_eq!(
+                "problems_name_their_files.log: no timestamps found",
+                e.to_string()
+            );
+        }
+        Ok(_) => panic!("this was supposed to throw an error"),
+    }
+    fs::remove_file(name).expect("could not delete file");
+}
+
+#[test]
+fn tolerated_misordering() {
+    let text = r#"
+2000-1-3 1:00:00 not it
+2000-1-3 2:00:01 what we're looking for
+2000-1-3 2:00:00 also what we're looking for
+2000-1-3 1:59:59 not it
+2000-1-3 2:30:00 this too
+2000-1-3 3:00:00 not it
+2000-1-3 2:59:59 but this
+2000-1-3 3:00:01 not it
+"#;
+    let search = Search::new(date("2000-1-3 2:00:00"), date("2000-1-3 3:00:00"));
+    let bounded = search.clone().start_line(3).end_line(4);
+    match bounded.run(Source::from(text.as_bytes())) {
+        Err(Problem::MisorderedTimestamps { .. }) => (),
+        Err(e) => panic!("wrong error: {:?}", e),
+        Ok(_) => panic!("this was supposed to throw an error"),
+    }
+    // a tolerance must exceed the inversion
+    match bounded
+        .clone()
+        .tolerance(Duration::seconds(1))
+        .run(Source::from(text.as_bytes()))
+    {
+        Err(Problem::MisorderedTimestamps { .. }) => (),
+        Err(e) => panic!("wrong error: {:?}", e),
+        Ok(_) => panic!("this was supposed to throw an error"),
+    }
+    match bounded
+        .tolerance(Duration::seconds(2))
+        .run(Source::from(text.as_bytes()))
+    {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            assert_eq!(2, lines.len());
+        }
+    }
+    match search
+        .tolerance(Duration::seconds(3))
+        .run(Source::from(text.as_bytes()))
+    {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(2, offset);
+            let found: Vec<&str> = lines.iter().map(|l| &l[17..]).collect();
+            assert_eq!(
+                vec![
+                    "what we're looking for",
+                    "also what we're looking for",
+                    "this too",
+                    "but this"
+                ],
+                found
+            );
+        }
+    }
+}
+
+#[test]
+fn tolerance_finds_every_line() {
+    // lines written by threads up to a second apart
+    let base = date("2000-1-1 0:00:00");
+    let mut text = String::new();
+    let mut times = vec![];
+    for i in 0..5000 {
+        let t = base + Duration::milliseconds(500 * i + random::<i64>().rem_euclid(1000));
+        text += &format!("{} line {}\n", t.format("%Y-%m-%d %H:%M:%S%.3f"), i);
+        times.push(t);
+    }
+    for _ in 0..20 {
+        let start = base + Duration::seconds(random::<i64>().rem_euclid(2400));
+        let end = start + Duration::seconds(random::<i64>().rem_euclid(60));
+        let expected: Vec<usize> = (0..times.len())
+            .filter(|&i| times[i] >= start && times[i] < end)
+            .collect();
+        let search = Search::new(start, end).tolerance(Duration::seconds(1));
+        match search.run(Source::from(text.as_bytes())) {
+            Err(e) => panic!("error: {:?}", e),
+            Ok((offset, lines)) => {
+                let found: Vec<usize> = lines
+                    .iter()
+                    .map(|l| l.rsplit(' ').next().unwrap().parse().unwrap())
+                    .collect();
+                assert_eq!(expected, found);
+                if let Some(&first) = expected.first() {
+                    assert_eq!(first, offset);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn tolerance_beyond_the_calendar() {
+    let text = "2000-01-01 00:00:00 before\n2000-01-01 00:00:01 in\n2000-01-01 00:00:02 after\n";
+    let search = Search::new(date("2000-1-1 0:00:01"), date("2000-1-1 0:00:02"))
+        .tolerance(Duration::days(100_000_000));
+    match search.run(Source::from(text.as_bytes())) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((offset, lines)) => {
+            assert_eq!(1, offset);
+            assert_eq!(vec!["2000-01-01 00:00:01 in"], lines);
+        }
+    }
+    let counted = search.count(Source::from(text.as_bytes())).unwrap();
+    assert_eq!(1, counted.first);
+}
+
+#[test]
+fn misordered_regions() {
+    // an ordered log but for a shuffled stretch in the middle
+    let base = date("2000-1-1 0:00:00");
+    let mut times: Vec<NaiveDateTime> = (0..10_000).map(|i| base + Duration::seconds(i)).collect();
+    for i in (4000..4400).rev() {
+        let j = 4000 + random::<usize>() % (i - 4000 + 1);
+        times.swap(i, j);
+    }
+    let text: String = times
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("{} line {}\n", t.format("%Y-%m-%d %H:%M:%S"), i))
+        .collect();
+    for &(from, to) in &[
+        (4100, 4110),
+        (3950, 4050),
+        (4300, 4500),
+        (4399, 4400),
+        (100, 200),
+    ] {
+        let start = base + Duration::seconds(from);
+        let end = base + Duration::seconds(to);
+        let expected: Vec<usize> = (0..times.len())
+            .filter(|&i| times[i] >= start && times[i] < end)
+            .collect();
+        let mut lines = Search::new(start, end)
+            .iter(Source::from(text.as_bytes()))
+            .expect("could not search");
+        let found: Vec<usize> = lines
+            .by_ref()
+            .map(|l| {
+                let l = String::from_utf8(l.unwrap()).unwrap();
+                l.trim_end().rsplit(' ').next().unwrap().parse().unwrap()
+            })
+            .collect();
+        assert_eq!(expected, found, "from {} to {}", from, to);
+        let regions = lines.misordered();
+        if to <= 200 {
+            assert!(regions.is_empty());
+        } else {
+            assert!(!regions.is_empty(), "from {} to {}", from, to);
+            for &(a, b) in regions {
+                assert!(
+                    a >= 3990 && b < 4410,
+                    "region {} - {} from {} to {} {:?}",
+                    a,
+                    b,
+                    from,
+                    to,
+                    regions
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn misordered_ends() {
+    // an ordered log but for shuffled stretches at either end, the first
+    // timestamp among the latest of its stretch and the last among the
+    // earliest, with an untimestamped line at either end
+    let base = date("2000-1-1 0:00:00");
+    let mut times: Vec<NaiveDateTime> = (0..10_000).map(|i| base + Duration::seconds(i)).collect();
+    for &(a, b) in &[(0, 50), (9950, 10_000)] {
+        for i in (a..b).rev() {
+            let j = a + random::<usize>() % (i - a + 1);
+            times.swap(i, j);
+        }
+    }
+    let latest = (0..50).max_by_key(|&i| times[i]).unwrap();
+    times.swap(0, latest);
+    let earliest = (9950..10_000).min_by_key(|&i| times[i]).unwrap();
+    times.swap(9999, earliest);
+    let text: String = times
+        .iter()
+        .enumerate()
+        .map(|(i, t)| format!("{} line {}\n", t.format("%Y-%m-%d %H:%M:%S"), i))
+        .collect();
+    let text = format!("  before\n{}  after\n", text);
+    let mut ranges = vec![(0, 10), (-10, 1), (9990, 10_000), (9999, 10_010)];
+    for _ in 0..100 {
+        let from = random::<i64>().rem_euclid(60);
+        let length = random::<i64>().rem_euclid(5) + 1;
+        ranges.push((from, from + length));
+        ranges.push((9940 + from, 9940 + from + length));
+    }
+    for (from, to) in ranges {
+        let start = base + Duration::seconds(from);
+        let end = base + Duration::seconds(to);
+        let expected: Vec<usize> = (0..times.len())
+            .filter(|&i| times[i] >= start && times[i] < end)
+            .collect();
+        let found: Vec<usize> = Search::new(start, end)
+            .iter(Source::from(text.as_bytes()))
+            .expect("could not search")
+            .filter_map(|l| {
+                let l = String::from_utf8(l.unwrap()).unwrap();
+                l.trim_end().rsplit(' ').next().unwrap().parse().ok()
+            })
+            .collect();
+        assert_eq!(expected, found, "from {} to {}", from, to);
+    }
+    // a range wholly beyond the log is still a problem
+    for &(from, to) in &[(-100, -10), (10_000, 10_100)] {
+        let start = base + Duration::seconds(from);
+        let search = Search::new(start, base + Duration::seconds(to));
+        assert!(search.iter(Source::from(text.as_bytes())).is_err());
+    }
+}
+
+#[test]
+fn repeated_timestamps() {
+    // several lines a second, so the start may fall on any of them
+    let base = date("2000-1-1 0:00:00");
+    let text: String = (0..30_000)
+        .map(|i| {
+            let t = base + Duration::seconds(i / 3);
+            format!("{} line {}\n", t.format("%Y-%m-%d %H:%M:%S"), i)
+        })
+        .collect();
+    for _ in 0..50 {
+        let from = random::<i64>().rem_euclid(10_000);
+        let start = base + Duration::seconds(from);
+        let search = Search::new(start, start + Duration::seconds(2));
+        match search.run(Source::from(text.as_bytes())) {
+            Err(e) => panic!("error: {:?}", e),
+            Ok((offset, lines)) => {
+                assert_eq!(3 * from as usize, offset, "from {}", from);
+                assert_eq!(6.min(30_000 - offset), lines.len(), "from {}", from);
+            }
+        }
+    }
+}
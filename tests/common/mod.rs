@@ -0,0 +1,78 @@
+// fixtures shared by the tests of searching logs; each test file uses only
+// some of them
+#![allow(dead_code)]
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use trufflehunter::{Extent, LineSource, Search, Source};
+
+pub fn date(y: i32, m: u32, d: u32, h: u32, mn: u32, s: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(y, m, d)
+        .and_then(|d| d.and_hms_opt(h, mn, s))
+        .unwrap()
+}
+
+// the time the logs of the tests begin
+pub fn start() -> NaiveDateTime {
+    date(2019, 1, 12, 0, 0, 0)
+}
+
+// a search for the lines from and to some seconds after the start
+pub fn search(from: i64, to: i64) -> Search {
+    Search::new(
+        start() + Duration::seconds(from),
+        start() + Duration::seconds(to),
+    )
+}
+
+// a line timestamped some time after the start, with a fraction of a second
+// only if there is one
+pub fn line(after: Duration, text: &str) -> String {
+    let t = start() + after;
+    format!("{} {}\n", t.format("%Y-%m-%d %H:%M:%S%.f"), text)
+}
+
+// a log of some number of lines, line i timestamped time(i) after the start
+// and followed by continued(i) lines without timestamps
+pub fn log_text<T, C>(lines: usize, time: T, continued: C) -> String
+where
+    T: Fn(usize) -> Duration,
+    C: Fn(usize) -> usize,
+{
+    let mut text = String::new();
+    for i in 0..lines {
+        text += &line(time(i), &format!("event {}", i));
+        text += &"  a continuation line\n".repeat(continued(i));
+    }
+    text
+}
+
+// where the lines a search finds by iterating over a log are and the lines
+// themselves, and what it finds in the same log some other way
+pub fn both_ways<T, F>(search: &Search, text: &[u8], other: F) -> ((Extent, Vec<Vec<u8>>), T)
+where
+    F: FnOnce(Source<Cursor<&[u8]>>) -> T,
+{
+    let mut source = Source::from(text);
+    let mut lines = search.iter(&mut source).unwrap();
+    let found: Vec<Vec<u8>> = lines.by_ref().map(Result::unwrap).collect();
+    let first = lines.offset();
+    let last = lines.last_index();
+    let misordered = lines.misordered().to_vec();
+    let end = last.map_or(first, |last| last + 1);
+    let iterated = Extent {
+        first,
+        last,
+        bytes: (source.offset(first), source.offset(end)),
+        misordered,
+    };
+    ((iterated, found), other(Source::from(text)))
+}
+
+// a fresh directory for a test's logs
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hun-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
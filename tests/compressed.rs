@@ -0,0 +1,306 @@
+// tests of compressed logs
+extern crate trufflehunter;
+use trufflehunter::{open_log, GzipSource, LineSource, Search, Source, ZstdSource};
+extern crate chrono;
+use chrono::Duration;
+extern crate flate2;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+#[macro_use]
+extern crate lazy_static;
+extern crate rand;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs;
+use std::io::{self, Cursor, Write};
+extern crate xz2;
+extern crate zstd;
+mod common;
+use common::{log_text, start};
+
+// a few megabytes of log text, enough for several checkpoints
+fn log() -> Vec<u8> {
+    let mut rng = StdRng::from_seed([7; 32]);
+    let mut ms = 0;
+    let lines: Vec<(i64, usize)> = (0..30_000)
+        .map(|_| {
+            ms += rng.gen_range(0, 1000);
+            (ms, rng.gen_range(0, 5))
+        })
+        .collect();
+    let time = |i: usize| Duration::milliseconds(lines[i].0);
+    log_text(lines.len(), time, |i| lines[i].1).into_bytes()
+}
+
+lazy_static! {
+    static ref TEXT: Vec<u8> = log();
+    static ref GZIPPED: Vec<u8> = gzip(&TEXT);
+    static ref SEEKABLE: Vec<u8> = seekable_zstd(&TEXT, 100_000, false);
+}
+
+fn gzip(text: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(vec![], Compression::fast());
+    encoder.write_all(text).unwrap();
+    encoder.finish().unwrap()
+}
+
+// compresses text into independent zstd frames of about frame_size bytes
+// followed by a seek table, optionally with checksums
+fn seekable_zstd(text: &[u8], frame_size: usize, checksums: bool) -> Vec<u8> {
+    let mut data = vec![];
+    let mut table = vec![];
+    let mut count = 0u32;
+    for chunk in text.chunks(frame_size) {
+        let frame = zstd::bulk::compress(chunk, 1).unwrap();
+        table.extend((frame.len() as u32).to_le_bytes());
+        table.extend((chunk.len() as u32).to_le_bytes());
+        if checksums {
+            // the checksums are not verified, so any will do
+            table.extend(0u32.to_le_bytes());
+        }
+        data.extend(frame);
+        count += 1;
+    }
+    table.extend(count.to_le_bytes());
+    table.push(if checksums { 0x80 } else { 0 });
+    table.extend(0x8F92_EAB1u32.to_le_bytes());
+    data.extend(0x184D_2A5Eu32.to_le_bytes());
+    data.extend((table.len() as u32).to_le_bytes());
+    data.extend(table);
+    data
+}
+
+fn search(minutes: i64) -> Search {
+    let s = start() + Duration::minutes(minutes);
+    Search::new(s, s + Duration::minutes(5))
+}
+
+#[test]
+fn gzip_random_access() {
+    let text = &*TEXT;
+    let mut source = GzipSource::new(Cursor::new(&GZIPPED[..])).unwrap();
+    let plain = Source::from(&text[..]);
+    assert_eq!(plain.len(), source.len());
+    assert_eq!(text.len() as u64, source.offset(source.len()));
+    // read backwards and forwards all over
+    let mut rng = StdRng::from_seed([3; 32]);
+    let mut buf = vec![0; 1000];
+    for _ in 0..50 {
+        let offset = rng.gen_range(0, text.len() as u64);
+        let n = source.read_at(offset, &mut buf).unwrap();
+        assert!(n > 0);
+        let o = offset as usize;
+        assert_eq!(&text[o..o + n], &buf[..n]);
+    }
+    for i in (0..source.len()).step_by(997).rev() {
+        assert_eq!(plain.offset(i), source.offset(i));
+        let start = plain.offset(i) as usize;
+        let end = plain.offset(i + 1) as usize;
+        assert_eq!(&text[start..end], &source.line(i).unwrap()[..]);
+    }
+}
+
+#[test]
+fn gzip_search() {
+    for minutes in [0, 100, 200, 2000] {
+        let expected = search(minutes).run(Source::from(&TEXT[..]));
+        let found = search(minutes).run(GzipSource::new(Cursor::new(&GZIPPED[..])).unwrap());
+        match (expected, found) {
+            (Ok(expected), Ok(found)) => assert_eq!(expected, found),
+            (Err(e), Err(f)) => assert_eq!(format!("{:?}", e), format!("{:?}", f)),
+            (e, f) => panic!("expected {:?}, found {:?}", e, f),
+        }
+    }
+}
+
+#[test]
+fn gzip_members() {
+    // appending to a gzipped log adds a member
+    let text = &*TEXT;
+    let half = text.len() / 2;
+    let mut data = gzip(&text[..half]);
+    data.extend(gzip(&text[half..]));
+    let mut source = GzipSource::new(Cursor::new(data)).unwrap();
+    let plain = Source::from(&text[..]);
+    assert_eq!(plain.len(), source.len());
+    let mut buf = vec![0; 100];
+    for offset in [text.len() - 100, half - 50, 10, half + 5] {
+        let n = source.read_at(offset as u64, &mut buf).unwrap();
+        assert_eq!(&text[offset..offset + n], &buf[..n]);
+    }
+}
+
+#[test]
+fn gzip_index() {
+    let text = &*TEXT;
+    let data = GZIPPED.clone();
+    let mut source = GzipSource::new(Cursor::new(data.clone())).unwrap();
+    let mut index = vec![];
+    source.write_index(&mut index).unwrap();
+    let mut indexed = GzipSource::with_index(Cursor::new(data.clone()), &index[..]).unwrap();
+    assert_eq!(source.len(), indexed.len());
+    for i in (0..source.len()).step_by(1009).rev() {
+        assert_eq!(source.offset(i), indexed.offset(i));
+        assert_eq!(source.line(i).unwrap(), indexed.line(i).unwrap());
+    }
+    assert_eq!(
+        search(200).run(source).unwrap(),
+        search(200).run(indexed).unwrap()
+    );
+    // the index does not fit other data
+    let other = gzip(&text[1..]);
+    assert!(GzipSource::with_index(Cursor::new(other), &index[..]).is_err());
+    assert!(GzipSource::with_index(Cursor::new(data), &index[..index.len() / 2]).is_err());
+}
+
+// a number as it is written in an index
+fn number(mut n: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    while n > 0x7f {
+        bytes.push((n & 0x7f) as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+    bytes
+}
+
+#[test]
+fn bad_gzip_index() {
+    let data = GZIPPED.clone();
+    let mut index = vec![];
+    GzipSource::new(Cursor::new(&data[..]))
+        .unwrap()
+        .write_index(&mut index)
+        .unwrap();
+    let header = [&b"hun gzip index 1\n"[..], &number(data.len() as u64)].concat();
+    let bad = vec![
+        // truncated anywhere
+        index[..index.len() - 1].to_vec(),
+        index[..index.len() / 3].to_vec(),
+        index[..header.len() + 2].to_vec(),
+        // more lines than there could be
+        [&header[..], &number(u64::MAX)].concat(),
+        [&header[..], &number(1 << 40), &[1; 1000]].concat(),
+        // offsets past the largest there could be
+        [
+            &header[..],
+            &number(2),
+            &number(u64::MAX),
+            &number(u64::MAX),
+        ]
+        .concat(),
+        // more checkpoints than there could be
+        [&header[..], &number(1), &number(5), &number(u64::MAX)].concat(),
+    ];
+    for index in bad {
+        let e = GzipSource::with_index(Cursor::new(&data[..]), &index[..]).err();
+        assert_eq!(Some(io::ErrorKind::InvalidData), e.map(|e| e.kind()));
+    }
+}
+
+#[test]
+fn bad_gzip() {
+    let data = &*GZIPPED;
+    // truncated
+    assert!(GzipSource::new(Cursor::new(&data[..data.len() / 2])).is_err());
+    // not gzip at all
+    assert!(GzipSource::new(Cursor::new(&b"2019-01-12 00:00:00 plain text\n"[..])).is_err());
+    // corrupt
+    let mut corrupt = data.to_vec();
+    for b in corrupt[1000..2000].iter_mut() {
+        *b = !*b;
+    }
+    assert!(GzipSource::new(Cursor::new(corrupt)).is_err());
+}
+
+#[test]
+fn open_by_magic() {
+    let text = b"2019-01-12 00:00:01 one\n2019-01-12 00:00:02 two\n";
+    let dir = std::env::temp_dir();
+    let plain = dir.join(format!("hun-plain-{}.log", std::process::id()));
+    let compressed = dir.join(format!("hun-compressed-{}.log", std::process::id()));
+    fs::write(&plain, text).unwrap();
+    fs::write(&compressed, gzip(text)).unwrap();
+    let a = open_log(&plain).and_then(|mut s| s.line(1));
+    let b = open_log(&compressed).and_then(|mut s| s.line(1));
+    fs::remove_file(&plain).ok();
+    fs::remove_file(&compressed).ok();
+    assert_eq!(b"2019-01-12 00:00:02 two\n", &a.unwrap()[..]);
+    assert_eq!(b"2019-01-12 00:00:02 two\n", &b.unwrap()[..]);
+}
+
+#[test]
+fn zstd_random_access() {
+    let text = &*TEXT;
+    let plain = Source::from(&text[..]);
+    for data in [SEEKABLE.clone(), seekable_zstd(&text[..], 77_777, true)] {
+        let mut source = ZstdSource::new(Cursor::new(data)).unwrap();
+        assert_eq!(plain.len(), source.len());
+        let mut rng = StdRng::from_seed([5; 32]);
+        let mut buf = vec![0; 1000];
+        for _ in 0..50 {
+            let offset = rng.gen_range(0, text.len() as u64);
+            let n = source.read_at(offset, &mut buf).unwrap();
+            assert!(n > 0);
+            let o = offset as usize;
+            assert_eq!(&text[o..o + n], &buf[..n]);
+        }
+        for i in (0..source.len()).step_by(997).rev() {
+            assert_eq!(plain.offset(i), source.offset(i));
+            let start = plain.offset(i) as usize;
+            let end = plain.offset(i + 1) as usize;
+            assert_eq!(&text[start..end], &source.line(i).unwrap()[..]);
+        }
+    }
+}
+
+#[test]
+fn zstd_search() {
+    for minutes in [0, 100, 200] {
+        let expected = search(minutes).run(Source::from(&TEXT[..])).unwrap();
+        let found = search(minutes)
+            .run(ZstdSource::new(Cursor::new(&SEEKABLE[..])).unwrap())
+            .unwrap();
+        assert_eq!(expected, found);
+    }
+}
+
+#[test]
+fn bad_zstd() {
+    let data = &*SEEKABLE;
+    // no seek table
+    let plain = zstd::encode_all(&TEXT[..], 1).unwrap();
+    assert!(ZstdSource::new(Cursor::new(plain)).is_err());
+    // truncated
+    assert!(ZstdSource::new(Cursor::new(&data[..data.len() / 2])).is_err());
+    // a seek table that does not fit the frames
+    let mut wrong = data.to_vec();
+    let n = wrong.len();
+    wrong[n - 12] ^= 1;
+    assert!(ZstdSource::new(Cursor::new(wrong)).is_err());
+}
+
+#[test]
+fn open_compressed_by_magic() {
+    let text = &TEXT[..];
+    let mut xz = xz2::write::XzEncoder::new(vec![], 1);
+    xz.write_all(text).unwrap();
+    let logs = [
+        ("seekable", SEEKABLE.clone()),
+        ("zstd", zstd::encode_all(text, 1).unwrap()),
+        ("xz", xz.finish().unwrap()),
+    ];
+    let expected = search(100).run(Source::from(text)).unwrap();
+    for (name, data) in logs.iter() {
+        let path = std::env::temp_dir().join(format!("hun-{}-{}.log", name, std::process::id()));
+        fs::write(&path, data).unwrap();
+        let found = open_log(&path).map(|s| {
+            let path_kept = s.path() == Some(&path);
+            (path_kept, search(100).run(s))
+        });
+        fs::remove_file(&path).ok();
+        let (path_kept, found) = found.unwrap();
+        assert!(path_kept, "{} log lost its path", name);
+        assert_eq!(expected, found.unwrap(), "{} log", name);
+    }
+}
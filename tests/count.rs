@@ -0,0 +1,102 @@
+// tests of counting the lines in a range without reading them
+extern crate trufflehunter;
+use trufflehunter::{Extent, Periods, Search, Source};
+extern crate chrono;
+use chrono::Duration;
+extern crate rand;
+use rand::random;
+mod common;
+use common::{both_ways, log_text, search};
+
+// three lines every two seconds, with an untimestamped line now and then and
+// a gap of an hour in the middle
+fn log(lines: usize) -> String {
+    let gap = |i| if i >= lines / 2 { 3600 } else { 0 };
+    log_text(
+        lines,
+        |i| Duration::seconds((i * 2 / 3) as i64 + gap(i)),
+        |i| (i % 100 == 7) as usize,
+    )
+}
+
+// what counting finds, what it should find according to the lines iterated
+// over, and the number of lines iterated over
+fn counted_and_iterated(text: &str, search: &Search) -> (Extent, Extent, usize) {
+    let ((iterated, lines), counted) =
+        both_ways(search, text.as_bytes(), |log| search.count(log).unwrap());
+    (counted, iterated, lines.len())
+}
+
+#[test]
+fn same_as_iterated() {
+    let text = log(30_000);
+    let mut ranges = vec![(0, 10), (0, 20_000), (9000, 10_000), (9999, 14_000)];
+    for _ in 0..100 {
+        let from = random::<u32>() as i64 % 23_000 - 10;
+        let length = random::<u32>() as i64 % 5000;
+        ranges.push((from, from + length));
+    }
+    for (from, to) in ranges {
+        let (counted, iterated, n) = counted_and_iterated(&text, &search(from, to));
+        assert_eq!(iterated, counted, "from {} to {}", from, to);
+        assert_eq!(n, counted.lines());
+    }
+}
+
+#[test]
+fn empty_ranges() {
+    let text = log(30_000);
+    // in the gap, and of no length
+    for &(from, to) in &[(10_500, 11_000), (5000, 5000)] {
+        let (counted, iterated, n) = counted_and_iterated(&text, &search(from, to));
+        assert_eq!(iterated, counted);
+        assert_eq!(0, n);
+        assert_eq!(0, counted.lines());
+        assert_eq!(None, counted.last);
+        assert_eq!(counted.bytes.0, counted.bytes.1);
+    }
+    let before = search(-100, -10).count(Source::from(text.as_bytes()));
+    let after = search(100_000, 100_010).count(Source::from(text.as_bytes()));
+    assert!(before.is_err());
+    assert!(after.is_err());
+}
+
+#[test]
+fn line_bounds() {
+    let text = log(30_000);
+    for &(a, b) in &[(1, 30_300), (2000, 9000), (8000, 8001), (8000, 8000)] {
+        let s = search(1000, 12_000).start_line(a).end_line(b);
+        let (counted, iterated, n) = counted_and_iterated(&text, &s);
+        assert_eq!(iterated, counted, "lines {} to {}", a, b);
+        assert_eq!(n, counted.lines());
+    }
+}
+
+#[test]
+fn misordered_line() {
+    let text = log(3000);
+    let cut = text[..text.len() / 4].rfind('\n').unwrap() + 1;
+    // a line far earlier than those around it
+    let misplaced = "2019-01-12 00:00:10 misplaced\n";
+    let text = format!("{}{}{}", &text[..cut], misplaced, &text[cut..]);
+    for &(from, to) in &[(300, 400), (480, 520), (400, 600), (600, 900)] {
+        let (counted, iterated, n) = counted_and_iterated(&text, &search(from, to));
+        assert_eq!(iterated, counted, "from {} to {}", from, to);
+        // the misplaced line is counted but not iterated over
+        let within = from <= 500 && to > 500;
+        assert_eq!(n + within as usize, counted.lines());
+    }
+}
+
+#[test]
+fn several_periods() {
+    let text = log(30_000);
+    let mut periods = Periods::new(Source::from(text.as_bytes()));
+    for i in 0..50 {
+        let s = search(i * 300, i * 300 + 1000);
+        let counted = s.count(Source::from(text.as_bytes())).unwrap();
+        assert_eq!(counted, periods.count(&s).unwrap());
+        let lines = periods.iter(&s).unwrap();
+        assert_eq!(counted.lines(), lines.count());
+    }
+}
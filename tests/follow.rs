@@ -0,0 +1,156 @@
+// tests of following a log as it is written
+extern crate trufflehunter;
+use trufflehunter::{Problem, Search, Source};
+extern crate chrono;
+use chrono::Duration;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+mod common;
+use common::{line, log_text, scratch, search};
+
+// a line timestamped some seconds after the start
+fn at(seconds: i64, text: &str) -> String {
+    line(Duration::seconds(seconds), text)
+}
+
+// a log of a line a second for the given number of seconds
+fn log(seconds: usize) -> String {
+    log_text(seconds, |i| Duration::seconds(i as i64), |_| 0)
+}
+
+fn append(path: &Path, text: &str) {
+    let mut log = OpenOptions::new().append(true).open(path).unwrap();
+    log.write_all(text.as_bytes()).unwrap();
+}
+
+// wait until the follower has found the line
+fn wait_for(followed: &Receiver<String>, line: &str) {
+    while followed.recv().unwrap() != line {}
+}
+
+// the text of all the lines followed and the ranges they were found in, while
+// the writer writes to the log, waiting as it needs to on the lines followed
+fn followed<W>(search: &Search, path: &Path, writer: W) -> (String, Vec<(usize, usize)>)
+where
+    W: FnOnce(&Path, Receiver<String>) + Send + 'static,
+{
+    let mut follow = search.follow(path).unwrap();
+    let (send, receive) = mpsc::channel();
+    let writer = {
+        let path = path.to_path_buf();
+        thread::spawn(move || writer(&path, receive))
+    };
+    let mut text = String::new();
+    for line in follow.by_ref() {
+        let line = String::from_utf8(line.unwrap()).unwrap();
+        text += &line;
+        send.send(line).ok();
+    }
+    writer.join().unwrap();
+    (text, follow.ranges().to_vec())
+}
+
+#[test]
+fn follows_a_growing_log() {
+    let dir = scratch("follow-growing");
+    let path = dir.join("app.log");
+    // the last line is still being written
+    fs::write(&path, log(100) + "2019-01-12 00:01:40 half").unwrap();
+    let (text, ranges) = followed(&search(98, 120), &path, |path, followed| {
+        wait_for(&followed, &at(99, "event 99"));
+        append(path, " a line\n");
+        append(path, "a line without a timestamp\n");
+        wait_for(&followed, "a line without a timestamp\n");
+        append(path, &(at(110, "more") + &at(119, "last")));
+        wait_for(&followed, &at(119, "last"));
+        append(path, &(at(120, "too late") + &at(121, "later")));
+    });
+    fs::remove_dir_all(&dir).ok();
+    let expected = at(98, "event 98")
+        + &at(99, "event 99")
+        + &at(100, "half a line")
+        + "a line without a timestamp\n"
+        + &at(110, "more")
+        + &at(119, "last");
+    assert_eq!(expected, text);
+    assert_eq!(vec![(98, 103)], ranges);
+}
+
+#[test]
+fn follows_rotation() {
+    let dir = scratch("follow-rotation");
+    let path = dir.join("app.log");
+    fs::write(&path, log(100)).unwrap();
+    let (text, ranges) = followed(&search(99, 110), &path, |path, followed| {
+        // the log is only renamed once the follower has it open
+        append(path, &at(101, "before rotation"));
+        wait_for(&followed, &at(101, "before rotation"));
+        fs::rename(path, path.with_extension("log.1")).unwrap();
+        // a writer may finish with the old log after it is renamed
+        append(&path.with_extension("log.1"), "unfinished");
+        fs::write(path, at(102, "new log")).unwrap();
+        wait_for(&followed, &at(102, "new log"));
+        append(path, &at(103, "before truncation"));
+        wait_for(&followed, &at(103, "before truncation"));
+        // the log is shorter than it was until the follower has found the
+        // line written after its truncation
+        fs::write(path, "").unwrap();
+        append(path, &at(104, "truncated"));
+        wait_for(&followed, &at(104, "truncated"));
+        append(path, &at(110, "the end"));
+    });
+    fs::remove_dir_all(&dir).ok();
+    let expected = at(99, "event 99")
+        + &at(101, "before rotation")
+        + "unfinished"
+        + &at(102, "new log")
+        + &at(103, "before truncation")
+        + &at(104, "truncated");
+    assert_eq!(expected, text);
+    assert_eq!(vec![(99, 101), (0, 1), (0, 0)], ranges);
+}
+
+#[test]
+fn waits_for_the_range() {
+    let dir = scratch("follow-waits");
+    let path = dir.join("app.log");
+    fs::write(&path, log(100)).unwrap();
+    let (text, ranges) = followed(&search(200, 210), &path, |path, _| {
+        append(path, &(at(150, "early") + &at(200, "found")));
+        append(path, &(at(201, "also found") + &at(210, "the end")));
+    });
+    let empty = dir.join("empty.log");
+    fs::write(&empty, "").unwrap();
+    let (first, _) = followed(&search(0, 10), &empty, |path, _| {
+        append(path, &(at(0, "first") + &at(10, "the end")));
+    });
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(at(200, "found") + &at(201, "also found"), text);
+    assert_eq!(vec![(101, 102)], ranges);
+    assert_eq!(at(0, "first"), first);
+}
+
+#[test]
+fn range_already_written() {
+    let dir = scratch("follow-written");
+    let path = dir.join("app.log");
+    fs::write(&path, log(100)).unwrap();
+    let expected: String = search(10, 20)
+        .iter(Source::open(&path).unwrap())
+        .unwrap()
+        .map(|line| String::from_utf8(line.unwrap()).unwrap())
+        .collect();
+    let (text, ranges) = followed(&search(10, 20), &path, |_, _| {});
+    let after = search(-100, -50).follow(&path).err();
+    let gz = dir.join("app.log.gz");
+    fs::write(&gz, [0x1f, 0x8b, 8, 0]).unwrap();
+    let compressed = search(10, 20).follow(&gz).err();
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(expected, text);
+    assert_eq!(vec![(10, 19)], ranges);
+    assert!(matches!(after, Some(Problem::LogAfter { .. })));
+    assert!(matches!(compressed, Some(Problem::Io { .. })));
+}
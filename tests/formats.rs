@@ -0,0 +1,306 @@
+// tests of timestamp format specifications
+extern crate trufflehunter;
+use trufflehunter::{
+    detect_format, parse_format, preset, strftime, Problem, Search, Source, Zone, DEFAULT_FORMAT,
+    PRESETS, PRESET_ALIASES,
+};
+extern crate regex;
+use regex::bytes::Regex;
+extern crate chrono;
+use chrono::{Duration, NaiveDateTime};
+mod common;
+use common::date;
+
+// the text of the lines found by searching the text with the format
+fn found(spec: &str, start: NaiveDateTime, end: NaiveDateTime, text: &str) -> Vec<String> {
+    let search = Search::new(start, end)
+        .format(parse_format(spec).unwrap())
+        .query_zone(Zone::utc());
+    match search.run(Source::from(text.as_bytes())) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((_, lines)) => lines,
+    }
+}
+
+#[test]
+fn iso() {
+    let text = r#"2019-01-12 02:59:59 before
+2019-01-12 03:00:01 during
+2019-01-12 03:00:02 after
+"#;
+    let start = date(2019, 1, 12, 3, 0, 0);
+    let end = date(2019, 1, 12, 3, 0, 2);
+    assert_eq!(
+        vec!["2019-01-12 03:00:01 during"],
+        found("%Y-%m-%d %H:%M:%S", start, end, text)
+    );
+    assert_eq!(
+        vec!["2019-01-12 03:00:01 during"],
+        found("%F %T", start, end, text)
+    );
+}
+
+#[test]
+fn apache() {
+    let text = r#"
+127.0.0.1 - - [12/Jan/2019:04:59:59 +0200] "GET / HTTP/1.1" 200 1
+127.0.0.1 - - [12/Jan/2019:05:00:01 +0200] "GET /a HTTP/1.1" 200 1
+127.0.0.1 - - [12/Jan/2019:05:00:02 +0200] "GET /b HTTP/1.1" 200 1
+"#;
+    let lines = found(
+        "[%d/%b/%Y:%T %z]",
+        date(2019, 1, 12, 3, 0, 0),
+        date(2019, 1, 12, 3, 0, 2),
+        text,
+    );
+    assert_eq!(1, lines.len());
+    assert!(lines[0].contains("GET /a"));
+}
+
+#[test]
+fn twelve_hour_clock_and_short_year() {
+    let text = r#"Sat, 01/12/19 11:59:59.5 AM morning
+Sat, 01/12/19 12:00:00.25 PM noon
+Sat, 01/12/19 1:00:00 PM afternoon
+"#;
+    let lines = found(
+        "%a, %D %l:%M:%S%.f %p",
+        date(2019, 1, 12, 12, 0, 0),
+        date(2019, 1, 12, 13, 0, 0),
+        text,
+    );
+    assert_eq!(vec!["Sat, 01/12/19 12:00:00.25 PM noon"], lines);
+}
+
+#[test]
+fn padded_days_and_whitespace() {
+    let text = "Jan  1 00:00:01 host one\nJan  2 00:00:01 host two\nJan 10 00:00:01 host ten\n";
+    let lines = found(
+        "%b %e %T",
+        date(2019, 1, 2, 0, 0, 0),
+        date(2019, 1, 10, 0, 0, 0),
+        text,
+    );
+    assert_eq!(vec!["Jan  2 00:00:01 host two"], lines);
+}
+
+#[test]
+fn raw_regexes() {
+    let text = "20190112T030001 one\n20190112T030002 two\n";
+    let lines = found(
+        r"regex:^(?P<year>\d{4})(?P<month>\d\d)(?P<day>\d\d)T(?P<hour>\d\d)(?P<minute>\d\d)(?P<second>\d\d)",
+        date(2019, 1, 12, 3, 0, 0),
+        date(2019, 1, 12, 3, 0, 2),
+        text,
+    );
+    assert_eq!(vec!["20190112T030001 one"], lines);
+}
+
+#[test]
+fn bad_formats() {
+    // unknown directive
+    assert!(strftime("%Y-%m-%d %Q").is_err());
+    // a trailing %
+    assert!(strftime("%Y-%m-%d %").is_err());
+    // the same part twice
+    assert!(strftime("%Y-%m-%d %T %H").is_err());
+    assert!(strftime("%F %Y").is_err());
+    // nothing about the time at all, most likely a regex without the prefix
+    assert!(parse_format(r"(?P<year>\d{4})").is_err());
+    assert!(strftime("100%%").is_err());
+    // a bad regex
+    assert!(parse_format("regex:(?P<year>").is_err());
+}
+
+#[test]
+fn literals_are_escaped() {
+    let rx = strftime("(%Y) %m.%d *%T*").unwrap();
+    assert!(rx.is_match(b"(2019) 01.12 *03:00:01*"));
+    assert!(!rx.is_match(b"2019 01x12 03:00:01"));
+}
+
+// assert that the sample line of the preset is found at the given time in UTC
+fn parses_sample(name: &str, time: NaiveDateTime) {
+    let preset = preset(name).unwrap();
+    let search = Search::new(time, time + Duration::seconds(1))
+        .format(Regex::new(preset.format).unwrap())
+        .log_zone(Zone::utc())
+        .query_zone(Zone::utc());
+    match search.run(Source::from(preset.sample.as_bytes())) {
+        Ok((_, lines)) => assert_eq!(vec![preset.sample], lines, "preset {}", name),
+        Err(e) => panic!("preset {}: {:?}", name, e),
+    }
+}
+
+#[test]
+fn apache_preset() {
+    parses_sample("apache", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn apache_error_preset() {
+    parses_sample("apache-error", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn default_preset() {
+    parses_sample("default", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn go_preset() {
+    parses_sample("go", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn iso8601_preset() {
+    parses_sample("iso8601", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn log4j_preset() {
+    parses_sample("log4j", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn nginx_preset() {
+    // another name for apache, as nginx access logs have the same timestamps
+    assert_eq!(preset("apache"), preset("nginx"));
+    let line =
+        r#"127.0.0.1 - - [12/Jan/2019:03:00:01 +0000] "GET / HTTP/1.1" 200 612 "-" "curl/7.64.0""#;
+    let rx = Regex::new(preset("nginx").unwrap().format).unwrap();
+    assert!(rx.is_match(line.as_bytes()));
+}
+
+#[test]
+fn python_preset() {
+    parses_sample("python", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn syslog_preset() {
+    // in memory the year is the one nearest the query
+    parses_sample("syslog", date(2019, 1, 12, 3, 0, 1));
+}
+
+#[test]
+fn presets() {
+    for (i, p) in PRESETS.iter().enumerate() {
+        assert!(Regex::new(p.format).is_ok(), "preset {}", p.name);
+        assert_eq!(Some(p), preset(p.name));
+        if i > 0 {
+            assert!(PRESETS[i - 1].name < p.name, "presets out of order");
+        }
+    }
+    for &(alias, name) in PRESET_ALIASES {
+        assert!(PRESETS.iter().all(|p| p.name != alias), "alias {}", alias);
+        assert_eq!(name, preset(alias).unwrap().name);
+    }
+}
+
+// the name of the preset detected for the text
+fn detected(text: &str) -> &'static str {
+    match detect_format(&mut Source::from(text.as_bytes())) {
+        Ok(p) => p.name,
+        Err(e) => panic!("error: {:?}", e),
+    }
+}
+
+#[test]
+fn detection() {
+    for p in PRESETS {
+        let name = detected(p.sample);
+        // formats that find the same timestamps in a sample are equally good
+        let rx = Regex::new(preset(name).unwrap().format).unwrap();
+        assert!(rx.is_match(p.sample.as_bytes()), "{} for {}", name, p.name);
+    }
+    assert_eq!("apache", detected(preset("apache").unwrap().sample));
+    assert_eq!("syslog", detected(preset("syslog").unwrap().sample));
+    assert_eq!(
+        "apache-error",
+        detected(preset("apache-error").unwrap().sample)
+    );
+    assert_eq!("default", detected(preset("default").unwrap().sample));
+}
+
+#[test]
+fn detection_samples_the_whole_log() {
+    // the head of the log is a banner without timestamps; only the middle and
+    // tail show what the format is
+    let mut text = String::new();
+    for i in 0..1000 {
+        text += &format!("banner line {}\n", i);
+    }
+    for i in 0..1000 {
+        text += &format!(
+            "Jan 12 03:{:02}:{:02} myhost app: {}\n",
+            i / 60 % 60,
+            i % 60,
+            i
+        );
+    }
+    assert_eq!("syslog", detected(&text));
+}
+
+#[test]
+fn detection_prefers_increasing_times() {
+    // the Go format finds the time each line was logged; the default finds a
+    // timestamp on more lines, but the ones in the messages are out of order
+    let mut text = String::new();
+    for i in 0..30 {
+        text += &format!("2019/01/12 03:00:{:02} processing\n", i);
+        text += &format!("record from 2018-{:02}-01 00:00:00\n", 12 - i % 12);
+    }
+    assert_eq!("go", detected(&text));
+}
+
+#[test]
+fn nothing_detected() {
+    match detect_format(&mut Source::from(&b"no\ntimestamps\nhere\n"[..])) {
+        Err(Problem::NoTimestamps { .. }) => (),
+        Err(e) => panic!("wrong error: {:?}", e),
+        Ok(p) => panic!("detected {}", p.name),
+    }
+}
+
+#[test]
+fn several_formats() {
+    // the log switched to ISO timestamps with an upgrade
+    let text = r#"Jan 12 03:00:01 host before
+Jan 12 03:10:00 host upgrading
+2019-01-12 03:20:00 upgraded
+2019-01-12 03:30:00 after
+"#;
+    let search =
+        Search::new(date(2019, 1, 12, 3, 5, 0), date(2019, 1, 12, 3, 25, 0)).formats(vec![
+            strftime("%b %e %T").unwrap(),
+            strftime("%F %T").unwrap(),
+        ]);
+    assert_eq!(
+        vec![
+            "Jan 12 03:10:00 host upgrading",
+            "2019-01-12 03:20:00 upgraded"
+        ],
+        search.run(Source::from(text.as_bytes())).unwrap().1
+    );
+}
+
+#[test]
+fn first_format_that_parses() {
+    // the first format matches every line but can only parse a timestamp on
+    // the second; the default format is used for the rest
+    let text = r#"Foo 12 2019 03:00:00 [2019-01-12 03:00:00] a
+Jan 12 2019 03:00:01 [2019-01-12 05:00:00] b
+Foo 12 2019 03:00:00 [2019-01-12 03:00:02] c
+"#;
+    let search = Search::new(date(2019, 1, 12, 3, 0, 1), date(2019, 1, 12, 3, 0, 2))
+        .formats(vec![
+            strftime("%b %d %Y %T").unwrap(),
+            Regex::new(DEFAULT_FORMAT).unwrap(),
+        ])
+        .query_zone(Zone::utc());
+    assert_eq!(
+        vec!["Jan 12 2019 03:00:01 [2019-01-12 05:00:00] b"],
+        search.run(Source::from(text.as_bytes())).unwrap().1
+    );
+}
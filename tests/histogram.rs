@@ -0,0 +1,113 @@
+// tests of histograms of the lines in a range
+extern crate trufflehunter;
+use trufflehunter::{Periods, Problem, Source, MAX_BUCKETS};
+extern crate chrono;
+use chrono::{Duration, NaiveDateTime};
+mod common;
+use common::{log_text, search, start};
+
+// the time of line i of the log after the start: busier at some times than
+// others, with a quiet hour in the middle
+fn after(i: usize) -> Duration {
+    let mut ms = (i as i64) * 700 + ((i * i) % 601) as i64;
+    if i >= 20_000 {
+        ms += 3_600_000;
+    }
+    Duration::milliseconds(ms)
+}
+
+// the number of untimestamped lines after line i of the log
+fn continued(i: usize) -> usize {
+    if i % 97 == 5 {
+        2
+    } else {
+        0
+    }
+}
+
+// forty thousand lines with an untimestamped line now and then
+fn log() -> String {
+    log_text(40_000, after, continued)
+}
+
+// the histogram made from the log and the one expected from the times of its
+// lines
+fn made_and_expected(from: i64, to: i64, width: i64) -> (Vec<(NaiveDateTime, usize)>, Vec<usize>) {
+    let s = search(from, to);
+    let width = Duration::seconds(width);
+    let found = s.histogram(Source::from(log().as_bytes()), width).unwrap();
+    let (a, b) = (Duration::seconds(from), Duration::seconds(to));
+    let mut expected = vec![];
+    for i in 0..40_000 {
+        let t = after(i);
+        if t >= a && t < b {
+            let k = ((t - a).num_milliseconds() / width.num_milliseconds()) as usize;
+            expected.resize(expected.len().max(k + 1), 0);
+            expected[k] += 1 + continued(i);
+        }
+    }
+    (found, expected)
+}
+
+#[test]
+fn bisected_buckets() {
+    // about twenty-five hundred lines to a bucket
+    let (found, expected) = made_and_expected(1000, 13_000, 1800);
+    let counts: Vec<_> = found.iter().map(|&(_, n)| n).collect();
+    assert_eq!(expected, counts);
+    let times: Vec<_> = found.iter().map(|&(t, _)| t).collect();
+    let every: Vec<_> = (0..7)
+        .map(|k| start() + Duration::seconds(1000 + k * 1800))
+        .collect();
+    assert_eq!(every, times);
+}
+
+#[test]
+fn counted_buckets() {
+    // about fifteen lines to a bucket
+    let (found, expected) = made_and_expected(1000, 2000, 10);
+    let counts: Vec<_> = found.iter().map(|&(_, n)| n).collect();
+    assert_eq!(expected, counts);
+    assert_eq!(100, found.len());
+}
+
+#[test]
+fn quiet_buckets() {
+    // the buckets of the quiet hour are empty, whichever way they are counted
+    for &width in &[60, 1200] {
+        let (found, mut expected) = made_and_expected(13_000, 20_000, width);
+        expected.resize(found.len(), 0);
+        let counts: Vec<_> = found.iter().map(|&(_, n)| n).collect();
+        assert_eq!(expected, counts, "{}s buckets", width);
+        assert!(counts.contains(&0));
+    }
+}
+
+#[test]
+fn same_as_count() {
+    let text = log();
+    let mut periods = Periods::new(Source::from(text.as_bytes()));
+    for &(from, to, width) in &[(0, 30_000, 600), (5000, 5100, 1), (100, 200, 1000)] {
+        let s = search(from, to);
+        let width = Duration::seconds(width);
+        let found = s.histogram(Source::from(text.as_bytes()), width).unwrap();
+        assert_eq!(found, periods.histogram(&s, width).unwrap());
+        let total: usize = found.iter().map(|&(_, n)| n).sum();
+        assert_eq!(periods.count(&s).unwrap().lines(), total);
+    }
+    let none = search(100, 100).histogram(Source::from(text.as_bytes()), Duration::seconds(1));
+    assert_eq!(Vec::<(NaiveDateTime, usize)>::new(), none.unwrap());
+}
+
+#[test]
+fn bucket_widths_at_the_limits() {
+    let text = log();
+    let width = Duration::days(100_000_000);
+    let whole = search(0, 40_000).histogram(Source::from(text.as_bytes()), width);
+    assert_eq!(vec![(start(), 40_000 + 2 * 413)], whole.unwrap());
+    let width = Duration::milliseconds(1);
+    let too_many = search(0, MAX_BUCKETS as i64).histogram(Source::from(text.as_bytes()), width);
+    assert!(matches!(too_many, Err(Problem::TooManyBuckets)));
+    let most = search(0, MAX_BUCKETS as i64 / 1000).histogram(Source::from(text.as_bytes()), width);
+    assert_eq!(MAX_BUCKETS, most.unwrap().len());
+}
@@ -0,0 +1,183 @@
+// tests of searching logs with a sparse index kept beside them
+extern crate trufflehunter;
+use trufflehunter::{logs_in, rotated_logs, IndexedSource, LineSource, Problem, Search, Source};
+extern crate chrono;
+use chrono::Duration;
+extern crate flate2;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration as Elapsed, SystemTime};
+mod common;
+use common::{both_ways, log_text, scratch, search};
+
+// three lines a second from the start, with an untimestamped line now and then;
+// enough to fill several spans of the index
+fn log(lines: usize) -> String {
+    log_text(
+        lines,
+        |i| Duration::seconds(i as i64 / 3),
+        |i| (i % 1000 == 7) as usize,
+    )
+}
+
+// the index of the first line found and the lines
+type Found = (usize, Vec<Vec<u8>>);
+
+// what is found without and with the index
+fn plain_and_indexed(search: &Search, path: &Path) -> (Found, Found) {
+    let text = fs::read(path).unwrap();
+    let ((plain, lines), indexed) = both_ways(search, &text, |_| {
+        let lines = search.iter_indexed(path).unwrap();
+        (lines.offset(), lines.map(Result::unwrap).collect())
+    });
+    ((plain.first, lines), indexed)
+}
+
+#[test]
+fn same_lines() {
+    let dir = scratch("same-lines");
+    let path = dir.join("app.log");
+    fs::write(&path, log(100_000)).unwrap();
+    let mut indexed = IndexedSource::open(&path).unwrap();
+    let mut plain = Source::open(&path).unwrap();
+    let sidecar = IndexedSource::index_path(&path);
+    let kept = sidecar.is_file();
+    let mut reopened = IndexedSource::open(&path).unwrap();
+    let same = |a: &mut dyn LineSource, b: &mut dyn LineSource| {
+        a.len() == b.len()
+            && (0..=a.len()).all(|i| a.offset(i) == b.offset(i))
+            && (0..a.len())
+                .step_by(997)
+                .all(|i| a.line(i).unwrap() == b.line(i).unwrap())
+    };
+    let same_fresh = same(&mut indexed, &mut plain);
+    let same_reopened = same(&mut reopened, &mut plain);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(dir.join("app.log.hunidx"), sidecar);
+    assert!(kept);
+    assert!(same_fresh);
+    assert!(same_reopened);
+}
+
+#[test]
+fn same_search() {
+    let dir = scratch("same-search");
+    let path = dir.join("app.log");
+    fs::write(&path, log(100_000)).unwrap();
+    let ranges = [
+        (0, 10),
+        (3000, 3100),
+        (12_000, 14_000),
+        (33_330, 40_000),
+        (-100, 5),
+        (5000, 5000),
+    ];
+    // the second time round the index has the timestamps of its samples
+    let results: Vec<_> = (0..2)
+        .flat_map(|_| ranges.iter())
+        .map(|&(from, to)| plain_and_indexed(&search(from, to), &path))
+        .collect();
+    let bounded = search(3000, 3100).start_line(2000).end_line(9500);
+    let (plain, indexed) = plain_and_indexed(&bounded, &path);
+    fs::remove_dir_all(&dir).ok();
+    for ((plain, indexed), &(from, to)) in results.iter().zip(ranges.iter().cycle()) {
+        assert_eq!(plain, indexed, "from {} to {}", from, to);
+    }
+    assert_eq!(plain, indexed);
+    assert!(!plain.1.is_empty());
+}
+
+#[test]
+fn growing_log() {
+    let dir = scratch("growing-log");
+    let path = dir.join("app.log");
+    let text = log(100_000);
+    // the log is indexed while its last line is only partly written
+    let cut = text[..text.len() / 2].rfind('\n').unwrap() + 12;
+    fs::write(&path, &text[..cut]).unwrap();
+    let before = plain_and_indexed(&search(16_000, 17_000), &path);
+    let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+    log.write_all(&text.as_bytes()[cut..]).unwrap();
+    drop(log);
+    let after = plain_and_indexed(&search(16_000, 17_000), &path);
+    let whole = plain_and_indexed(&search(0, 40_000), &path);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(before.0, before.1);
+    assert_eq!(after.0, after.1);
+    assert!(after.0 .1.len() > before.0 .1.len());
+    assert_eq!(whole.0, whole.1);
+    assert_eq!(100_000 + 100, whole.1 .1.len());
+}
+
+#[test]
+fn rewritten_log() {
+    let dir = scratch("rewritten-log");
+    let path = dir.join("app.log");
+    let text = log(100_000);
+    fs::write(&path, &text).unwrap();
+    let first = plain_and_indexed(&search(20_000, 20_100), &path);
+    let later = SystemTime::now() + Elapsed::from_secs(60);
+    let rewrite = |text: &str| {
+        fs::write(&path, text).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|f| f.set_modified(later))
+            .unwrap();
+    };
+    // a shorter log, as after truncation
+    rewrite(&log(80_000)[1000..]);
+    let shorter = plain_and_indexed(&search(20_000, 20_100), &path);
+    // a log of the same size but with lines of different lengths
+    let rewritten =
+        text.replacen(" event 1\n", " e 1\n", 1)
+            .replacen(" event 0\n", " event 0 and\n", 1);
+    assert_eq!(text.len(), rewritten.len());
+    rewrite(&rewritten);
+    let same_size = plain_and_indexed(&search(0, 100), &path);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(first.0, first.1);
+    assert_eq!(shorter.0, shorter.1);
+    assert_eq!(same_size.0, same_size.1);
+    assert!(String::from_utf8_lossy(&same_size.1 .1[0]).ends_with("event 0 and\n"));
+}
+
+#[test]
+fn changed_while_open() {
+    let dir = scratch("changed-while-open");
+    let path = dir.join("app.log");
+    let text = log(100_000);
+    fs::write(&path, &text).unwrap();
+    let mut source = IndexedSource::open(&path).unwrap();
+    // the lines are no longer where the index has them
+    let moved = text.replace(" event ", "\nevent ");
+    fs::write(&path, &moved[..text.len()]).unwrap();
+    let counted = search(20_000, 20_100).count(&mut source);
+    fs::remove_dir_all(&dir).ok();
+    assert!(matches!(counted, Err(Problem::Io { .. })), "{:?}", counted);
+}
+
+#[test]
+fn compressed_and_listed_logs() {
+    let dir = scratch("compressed-and-listed");
+    let text = log(3000);
+    let mut encoder = GzEncoder::new(vec![], Compression::fast());
+    encoder.write_all(text.as_bytes()).unwrap();
+    fs::write(dir.join("app.log.1.gz"), encoder.finish().unwrap()).unwrap();
+    fs::write(dir.join("app.log"), &text).unwrap();
+    let compressed = search(100, 200).iter_indexed(dir.join("app.log.1.gz"));
+    let compressed = compressed.map(|lines| lines.count());
+    let unindexed = !IndexedSource::index_path(dir.join("app.log.1.gz")).exists();
+    search(100, 200).iter_indexed(dir.join("app.log")).unwrap();
+    let rotated = rotated_logs(dir.join("app.log"));
+    let listed = logs_in(&dir);
+    fs::remove_dir_all(&dir).ok();
+    assert_eq!(300, compressed.unwrap());
+    assert!(unindexed);
+    let expected = vec![dir.join("app.log"), dir.join("app.log.1.gz")];
+    assert_eq!(expected, rotated.unwrap());
+    assert_eq!(expected, listed.unwrap());
+}
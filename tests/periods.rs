@@ -0,0 +1,129 @@
+// tests of searching one log for several periods
+extern crate trufflehunter;
+use trufflehunter::{LineSource, Periods, Problem, Search, Source};
+extern crate chrono;
+use chrono::Duration;
+extern crate rand;
+use rand::random;
+use std::cell::Cell;
+use std::io;
+use std::rc::Rc;
+mod common;
+use common::{log_text, search};
+
+// lines at uneven intervals, busier at some times than others, with an
+// untimestamped line now and then
+fn log(lines: usize) -> String {
+    let mut ms = 0;
+    let times: Vec<i64> = (0..lines)
+        .map(|i| {
+            ms += ((i * i) % 997) as i64 * 7;
+            ms
+        })
+        .collect();
+    log_text(
+        lines,
+        |i| Duration::milliseconds(times[i]),
+        |i| (i % 500 == 3) as usize,
+    )
+}
+
+// a source that counts the reads made of it
+struct Counted<S> {
+    source: S,
+    reads: Rc<Cell<usize>>,
+}
+
+impl<S: LineSource> LineSource for Counted<S> {
+    fn len(&self) -> usize {
+        self.source.len()
+    }
+    fn offset(&self, i: usize) -> u64 {
+        self.source.offset(i)
+    }
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.reads.set(self.reads.get() + 1);
+        self.source.read_at(offset, buf)
+    }
+}
+
+// the index of the first line found and the lines, or the problem
+fn found<S: LineSource>(
+    lines: Result<trufflehunter::Lines<S>, Problem>,
+) -> Result<(usize, Vec<Vec<u8>>), String> {
+    match lines {
+        Ok(lines) => Ok((lines.offset(), lines.map(Result::unwrap).collect())),
+        Err(p) => Err(format!("{:?}", std::mem::discriminant(&p))),
+    }
+}
+
+// what each search finds when the log is opened once and when it is opened
+// for each search
+fn both_ways(text: &str, searches: &[Search]) {
+    let mut periods = Periods::new(Source::from(text.as_bytes()));
+    for s in searches {
+        let separate = found(s.iter(Source::from(text.as_bytes())));
+        assert_eq!(separate, found(periods.iter(s)), "{:?}", s);
+    }
+}
+
+#[test]
+fn same_as_separate_searches() {
+    let text = log(20_000);
+    let mut searches = vec![
+        search(-100, -50),
+        search(-100, 5),
+        search(0, 10),
+        search(60_000, 70_000),
+        search(1000, 1000),
+        search(1000, 1001),
+    ];
+    for _ in 0..100 {
+        let from = random::<u32>() as i64 % 75_000 - 1000;
+        let length = random::<u32>() as i64 % 100;
+        searches.push(search(from, from + length));
+    }
+    both_ways(&text, &searches);
+    let bounded = search(5000, 6000).start_line(3000).end_line(12_000);
+    both_ways(&text, &[search(5000, 6000), bounded, search(5100, 5200)]);
+}
+
+#[test]
+fn misordered_log() {
+    let text = log(20_000);
+    let cut = text[..text.len() / 2].rfind('\n').unwrap() + 1;
+    // a later stretch of the log is copied into the middle of it
+    let misplaced = &text[text.len() * 3 / 4..];
+    let misplaced = &misplaced[misplaced.find('\n').unwrap() + 1..];
+    let text = format!("{}{}{}", &text[..cut], misplaced, &text[cut..]);
+    let searches: Vec<_> = (0..50)
+        .map(|i| search(i * 1500, i * 1500 + 60))
+        .chain((0..50).map(|i| search(i * 1500 + 700, i * 1500 + 760)))
+        .collect();
+    both_ways(&text, &searches);
+}
+
+#[test]
+fn later_searches_begin_nearby() {
+    let text = log(100_000);
+    let reads = Rc::new(Cell::new(0));
+    let source = Counted {
+        source: Source::from(text.as_bytes()),
+        reads: reads.clone(),
+    };
+    let mut periods = Periods::new(source);
+    // the reads made looking for the start of a period
+    let mut reads_for = |s: &Search| {
+        reads.set(0);
+        periods.iter(s).unwrap();
+        reads.get()
+    };
+    let unaided = reads_for(&search(199_000, 199_010));
+    reads_for(&search(199_100, 199_110));
+    // a period between those already found begins between them
+    let aided = reads_for(&search(199_050, 199_060));
+    // and the same period again is found within a few lines
+    let again = reads_for(&search(199_050, 199_060));
+    assert!(aided <= unaided, "{} reads, then {}", unaided, aided);
+    assert!(again < 10, "{} reads", again);
+}
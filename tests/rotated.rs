@@ -0,0 +1,219 @@
+// tests of searching several logs as one timeline, such as rotated logs
+extern crate trufflehunter;
+use trufflehunter::{logs_in, open_log, rotated_logs, Problem, Search, Source};
+extern crate chrono;
+use chrono::Duration;
+extern crate flate2;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::PathBuf;
+mod common;
+use common::{log_text, scratch};
+
+// lines every two seconds from the start
+fn log(lines: usize) -> String {
+    log_text(lines, |i| Duration::seconds(2 * i as i64), |_| 0)
+}
+
+// the text split into three logs, given newest first as rotation names them
+fn rotated(text: &str) -> Vec<Source<Cursor<Vec<u8>>>> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let third = lines.len() / 3;
+    vec![
+        Source::from(lines[2 * third..].concat().into_bytes()),
+        Source::from(lines[third..2 * third].concat().into_bytes()),
+        Source::from(lines[..third].concat().into_bytes()),
+    ]
+}
+
+// the logs span hours, so searches are in minutes after the start
+fn search(from: i64, to: i64) -> Search {
+    common::search(60 * from, 60 * to)
+}
+
+// all the lines found, and how many logs they were found in
+fn found(search: &Search, sources: Vec<Source<Cursor<Vec<u8>>>>) -> (Vec<Vec<u8>>, usize) {
+    let found = search.iter_all(sources).unwrap();
+    let logs = found.len();
+    let lines = found.into_iter().flatten().map(Result::unwrap).collect();
+    (lines, logs)
+}
+
+#[test]
+fn one_timeline() {
+    let text = log(3000);
+    // the logs break at 00:33:20 and 01:06:40
+    for &(from, to, logs) in &[
+        (30, 40, 2),
+        (0, 100, 3),
+        (20, 30, 1),
+        (64, 65, 1),
+        (66, 67, 2),
+        (-10, 1, 1),
+    ] {
+        let s = search(from, to);
+        let expected: Vec<Vec<u8>> = s
+            .iter(Source::from(text.clone().into_bytes()))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let (lines, n) = found(&s, rotated(&text));
+        assert_eq!(expected, lines, "from {} to {}", from, to);
+        assert_eq!(logs, n, "from {} to {}", from, to);
+    }
+}
+
+#[test]
+fn logs_without_timestamps_are_passed_over() {
+    let text = log(300);
+    let mut sources = rotated(&text);
+    sources.insert(0, Source::from(vec![]));
+    sources.push(Source::from(b"nothing to see here\n".to_vec()));
+    let (lines, logs) = found(&search(0, 100), sources);
+    assert_eq!(300, lines.len());
+    assert_eq!(3, logs);
+}
+
+#[test]
+fn nothing_to_find() {
+    let text = log(300);
+    match search(-100, -50).iter_all(rotated(&text)) {
+        Err(Problem::LogAfter { .. }) => (),
+        Err(e) => panic!("unexpected problem: {}", e),
+        Ok(_) => panic!("found lines before the logs"),
+    }
+    match search(100, 150).iter_all(rotated(&text)) {
+        Err(Problem::LogBefore { .. }) => (),
+        Err(e) => panic!("unexpected problem: {}", e),
+        Ok(_) => panic!("found lines after the logs"),
+    }
+    match search(0, 100).iter_all(vec![Source::from(b"no times\n".to_vec())]) {
+        Err(Problem::NoTimestamps { .. }) => (),
+        Err(e) => panic!("unexpected problem: {}", e),
+        Ok(_) => panic!("found lines without timestamps"),
+    }
+}
+
+#[test]
+fn rotation_family() {
+    let dir = scratch("rotated");
+    let text = log(900);
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    fs::write(dir.join("app.log"), lines[600..].concat()).unwrap();
+    fs::write(dir.join("app.log.1"), lines[300..600].concat()).unwrap();
+    let mut encoder = GzEncoder::new(vec![], Compression::fast());
+    encoder.write_all(lines[..300].concat().as_bytes()).unwrap();
+    fs::write(dir.join("app.log.2.gz"), encoder.finish().unwrap()).unwrap();
+    fs::write(dir.join("other.log"), "2019-01-12 00:10:00 elsewhere\n").unwrap();
+    let paths = rotated_logs(dir.join("app.log"));
+    let results = paths.as_ref().ok().map(|paths| {
+        let logs = paths.iter().map(|p| open_log(p).unwrap());
+        let found = search(0, 60).iter_all(logs).unwrap();
+        let logs: Vec<Option<PathBuf>> = found
+            .iter()
+            .map(|lines| lines.path().map(|p| p.to_path_buf()))
+            .collect();
+        let count = found.into_iter().flatten().count();
+        (logs, count)
+    });
+    fs::remove_dir_all(&dir).ok();
+    let names: Vec<&str> = vec!["app.log", "app.log.1", "app.log.2.gz"];
+    let expected: Vec<PathBuf> = names.iter().map(|n| dir.join(n)).collect();
+    assert_eq!(expected, paths.unwrap());
+    let (logs, count) = results.unwrap();
+    let in_order: Vec<Option<PathBuf>> = expected.into_iter().rev().map(Some).collect();
+    assert_eq!(in_order, logs);
+    assert_eq!(900, count);
+}
+
+#[test]
+fn ends_without_timestamps() {
+    let dir = scratch("ends");
+    let text = log(3000);
+    // more untimestamped lines than are read from either end of a log
+    let trace = "    at some.deeply.nested.Frame(Frame.java:123)\n".repeat(2000);
+    fs::write(dir.join("trace.log"), text.clone() + &trace).unwrap();
+    fs::write(dir.join("banner.log"), trace.clone() + &text).unwrap();
+    let found: Vec<_> = ["trace.log", "banner.log"]
+        .iter()
+        .flat_map(|name| vec![(name, 0, 5), (name, 95, 100)])
+        .map(|(name, from, to)| {
+            let lines: Vec<Vec<u8>> = search(from, to)
+                .iter_paths(vec![dir.join(name)])
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .map(Result::unwrap)
+                .collect();
+            let expected: Vec<Vec<u8>> = search(from, to)
+                .iter(Source::open(dir.join(name)).unwrap())
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            (expected, lines)
+        })
+        .collect();
+    fs::remove_dir_all(&dir).ok();
+    for (expected, lines) in found {
+        assert!(!expected.is_empty());
+        assert_eq!(expected, lines);
+    }
+}
+
+#[test]
+fn directory() {
+    let dir = scratch("directory");
+    fs::create_dir_all(dir.join("old")).unwrap();
+    // logs big enough to be spanned from their ends alone, breaking every 150 minutes
+    let text = log(4 * 4500);
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    for (hour, chunk) in lines.chunks(4500).enumerate() {
+        fs::write(dir.join(format!("{:02}.log", hour)), chunk.concat()).unwrap();
+    }
+    fs::write(dir.join("notes.txt"), "nothing to see here\n").unwrap();
+    fs::write(dir.join(".hidden"), "2019-01-12 00:10:00 hidden\n").unwrap();
+    fs::write(dir.join("old").join("23.log"), "2019-01-11 23:10:00 old\n").unwrap();
+    let paths = logs_in(&dir);
+    let found = paths.as_ref().ok().map(|paths| {
+        [(140, 160), (0, 600), (200, 201)]
+            .iter()
+            .map(|&(from, to)| {
+                let found = search(from, to).iter_paths(paths).unwrap();
+                let logs = found.len();
+                let lines: Vec<Vec<u8>> = found.into_iter().flatten().map(Result::unwrap).collect();
+                (lines, logs)
+            })
+            .collect::<Vec<_>>()
+    });
+    let nothing = paths
+        .as_ref()
+        .ok()
+        .map(|paths| search(700, 800).iter_paths(paths).is_err());
+    fs::remove_dir_all(&dir).ok();
+    let names: Vec<String> = paths
+        .unwrap()
+        .iter()
+        .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(
+        vec!["00.log", "01.log", "02.log", "03.log", "notes.txt"],
+        names
+    );
+    for ((lines, logs), &(from, to, expected_logs)) in
+        found
+            .unwrap()
+            .into_iter()
+            .zip(&[(140, 160, 2), (0, 600, 4), (200, 201, 1)])
+    {
+        let expected: Vec<Vec<u8>> = search(from, to)
+            .iter(Source::from(text.clone().into_bytes()))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(expected, lines, "from {} to {}", from, to);
+        assert_eq!(expected_logs, logs, "from {} to {}", from, to);
+    }
+    assert!(nothing.unwrap());
+}
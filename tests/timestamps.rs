@@ -0,0 +1,330 @@
+// tests of the various ways timestamps can be written
+extern crate trufflehunter;
+use trufflehunter::{Problem, Search, Source, Zone};
+extern crate chrono;
+use chrono::Duration;
+extern crate regex;
+use regex::bytes::Regex;
+mod common;
+use common::date;
+
+fn zone(s: &str) -> Zone {
+    s.parse().unwrap()
+}
+
+// the text of the lines found by a search of the text
+fn found(search: Search, text: &str) -> Vec<String> {
+    match search.run(Source::from(text.as_bytes())) {
+        Err(e) => panic!("error: {:?}", e),
+        Ok((_, lines)) => lines,
+    }
+}
+
+#[test]
+fn offsets() {
+    let text = r#"
+2000-01-03T01:00:00Z one
+2000-01-03T03:30:00+02:00 two
+2000-01-03T01:00:00-0100 three
+2000-01-03T03:00:00Z four
+"#;
+    let search =
+        Search::new(date(2000, 1, 3, 0, 30, 0), date(2000, 1, 3, 2, 30, 0)).query_zone(Zone::utc());
+    assert_eq!(
+        vec![
+            "2000-01-03T01:00:00Z one",
+            "2000-01-03T03:30:00+02:00 two",
+            "2000-01-03T01:00:00-0100 three"
+        ],
+        found(search, text)
+    );
+    let search = Search::new(date(2000, 1, 3, 4, 15, 0), date(2000, 1, 3, 4, 45, 0))
+        .query_zone(zone("+0300"));
+    assert_eq!(vec!["2000-01-03T03:30:00+02:00 two"], found(search, text));
+}
+
+#[test]
+fn tz_capture() {
+    let text = r#"
+[03/01/2000 01:00:00 UTC] one
+[03/01/2000 03:00:00 +0100] two
+[03/01/2000 03:00:00 UTC] three
+"#;
+    let rx = Regex::new(
+        r"\[(?P<day>\d\d)/(?P<month>\d\d)/(?P<year>\d{4}) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d) (?P<tz>\S+)\]",
+    )
+    .unwrap();
+    let search = Search::new(date(2000, 1, 3, 2, 0, 0), date(2000, 1, 3, 3, 0, 0))
+        .format(rx)
+        .query_zone(Zone::utc());
+    assert_eq!(vec!["[03/01/2000 03:00:00 +0100] two"], found(search, text));
+}
+
+#[test]
+fn log_zone() {
+    let text = r#"
+2000-01-03 01:00:00 one
+2000-01-03 02:00:00 two
+2000-01-03 03:00:00 three
+"#;
+    // the log is in UTC and the query 5 hours behind it
+    let search = Search::new(date(2000, 1, 2, 20, 30, 0), date(2000, 1, 2, 21, 30, 0))
+        .log_zone(Zone::utc())
+        .query_zone(zone("-05:00"));
+    assert_eq!(vec!["2000-01-03 02:00:00 two"], found(search, text));
+    // without a log zone the timestamps are taken to be in the query's zone
+    let search = Search::new(date(2000, 1, 3, 1, 30, 0), date(2000, 1, 3, 2, 30, 0))
+        .query_zone(zone("-05:00"));
+    assert_eq!(vec!["2000-01-03 02:00:00 two"], found(search, text));
+}
+
+#[test]
+fn zone_parsing() {
+    assert_eq!(Zone::utc(), zone("UTC"));
+    assert_eq!(Zone::utc(), zone("z"));
+    assert_eq!(zone("+05:30"), zone("+0530"));
+    assert_eq!(Zone::Local, zone("Local"));
+    assert!("+5".parse::<Zone>().is_err());
+    assert!("+05:75".parse::<Zone>().is_err());
+}
+
+#[test]
+fn fractions() {
+    let mut text = String::new();
+    for ms in 0..1000 {
+        text += &format!("2000-01-03 01:00:00.{:03} line {}\n", ms, ms);
+        if ms % 7 == 0 {
+            text += "garbage\n";
+        }
+    }
+    let start = date(2000, 1, 3, 1, 0, 0) + Duration::milliseconds(500);
+    let search = Search::new(start, start + Duration::milliseconds(2));
+    assert_eq!(
+        vec![
+            "2000-01-03 01:00:00.500 line 500",
+            "2000-01-03 01:00:00.501 line 501"
+        ],
+        found(search, &text)
+    );
+}
+
+#[test]
+fn fraction_digits() {
+    let text = r#"
+2000-01-03 01:00:00,5 half
+2000-01-03 01:00:00,500001 just after half
+2000-01-03 01:00:00.999999999999 nearly a second
+2000-01-03 01:00:01 a second
+"#;
+    let start = date(2000, 1, 3, 1, 0, 0);
+    let search = Search::new(
+        start + Duration::microseconds(500_001),
+        start + Duration::seconds(1),
+    );
+    assert_eq!(
+        vec![
+            "2000-01-03 01:00:00,500001 just after half",
+            "2000-01-03 01:00:00.999999999999 nearly a second"
+        ],
+        found(search, text)
+    );
+}
+
+#[test]
+fn apache_month_names() {
+    let text = r#"
+127.0.0.1 - - [12/Jan/2019:02:59:59 +0000] "GET / HTTP/1.1" 200 1
+127.0.0.1 - - [12/Jan/2019:03:00:01 +0000] "GET /a HTTP/1.1" 200 1
+127.0.0.1 - - [12/Jan/2019:03:00:02 +0000] "GET /b HTTP/1.1" 200 1
+"#;
+    let rx = Regex::new(
+        r"\[(?P<day>\d\d)/(?P<month>\w+)/(?P<year>\d{4}):(?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d) (?P<offset>[-+]\d{4})\]",
+    )
+    .unwrap();
+    let search = Search::new(date(2019, 1, 12, 3, 0, 0), date(2019, 1, 12, 3, 0, 2))
+        .format(rx)
+        .query_zone(Zone::utc());
+    let lines = found(search, text);
+    assert_eq!(1, lines.len());
+    assert!(lines[0].contains("GET /a"));
+}
+
+#[test]
+fn month_names() {
+    let rx = Regex::new(
+        r"^(?P<month>[A-Za-z.]+) (?P<day>\d+) (?P<year>\d{4}) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)",
+    )
+    .unwrap();
+    let months = [
+        "Jan", "february", "MAR", "Apr.", "May", "June", "jul", "Aug", "Sept", "Oct", "Nov",
+        "December",
+    ];
+    let mut text = String::new();
+    for m in months.iter() {
+        text += &format!("{} 1 2019 00:00:00 {}\n", m, m);
+    }
+    for (i, m) in months.iter().enumerate() {
+        let start = date(2019, i as u32 + 1, 1, 0, 0, 0);
+        let search = Search::new(start, start + Duration::seconds(1)).format(rx.clone());
+        assert_eq!(
+            vec![format!("{} 1 2019 00:00:00 {}", m, m)],
+            found(search, &text)
+        );
+    }
+    let search = Search::new(date(2020, 1, 1, 0, 0, 0), date(2020, 1, 2, 0, 0, 0)).format(rx);
+    match search.run(Source::from(&b"Ja 1 2020 00:00:00 too short\n"[..])) {
+        Err(Problem::NoTimestamps { .. }) => (),
+        Err(e) => panic!("wrong error: {:?}", e),
+        Ok(_) => panic!("this was supposed to throw an error"),
+    }
+}
+
+#[test]
+fn ampm() {
+    let rx = Regex::new(
+        r"^(?P<month>\w+) (?P<day>\d+) (?P<year>\d{4}) (?P<hour>\d+):(?P<minute>\d\d):(?P<second>\d\d) (?P<ampm>[AaPp]\.?[Mm]\.?)",
+    )
+    .unwrap();
+    let text = r#"Jan 12 2019 12:00:01 AM just after midnight
+Jan 12 2019 3:00:01 AM early
+Jan 12 2019 11:59:59 a.m. just before noon
+Jan 12 2019 12:00:00 PM noon
+Jan 12 2019 3:00:01 pm afternoon
+Jan 12 2019 11:00:00 p.m. late
+"#;
+    let cases = [
+        (
+            date(2019, 1, 12, 0, 0, 0),
+            date(2019, 1, 12, 1, 0, 0),
+            "midnight",
+        ),
+        (
+            date(2019, 1, 12, 3, 0, 0),
+            date(2019, 1, 12, 4, 0, 0),
+            "early",
+        ),
+        (
+            date(2019, 1, 12, 11, 0, 0),
+            date(2019, 1, 12, 12, 0, 0),
+            "noon",
+        ),
+        (
+            date(2019, 1, 12, 12, 0, 0),
+            date(2019, 1, 12, 13, 0, 0),
+            "noon",
+        ),
+        (
+            date(2019, 1, 12, 15, 0, 0),
+            date(2019, 1, 12, 16, 0, 0),
+            "afternoon",
+        ),
+        (
+            date(2019, 1, 12, 23, 0, 0),
+            date(2019, 1, 13, 0, 0, 0),
+            "late",
+        ),
+    ];
+    for (start, end, expected) in cases.iter() {
+        let lines = found(Search::new(*start, *end).format(rx.clone()), text);
+        assert_eq!(1, lines.len());
+        assert!(lines[0].ends_with(expected));
+    }
+}
+
+#[test]
+fn epoch() {
+    let start = date(2019, 1, 12, 3, 0, 0);
+    let base = start.and_utc().timestamp();
+    let mut seconds = String::new();
+    let mut millis = String::new();
+    let mut micros = String::new();
+    let mut nanos = String::new();
+    for i in 0..500 {
+        let s = base - 250 + i;
+        seconds += &format!("{}.{:03} line {}\n", s, i, i);
+        millis += &format!("{}{:03} line {}\n", s, i, i);
+        micros += &format!("{}{:06} line {}\n", s, i, i);
+        nanos += &format!("{}{:09} line {}\n", s, i, i);
+    }
+    let cases = [
+        (r"^(?P<epoch>\d+\.\d+) ", seconds),
+        (r"^(?P<epoch_ms>\d+) ", millis),
+        (r"^(?P<epoch_us>\d+) ", micros),
+        (r"^(?P<epoch_ns>\d+) ", nanos),
+    ];
+    for (rx, text) in cases.iter() {
+        let search = Search::new(start, start + Duration::seconds(2))
+            .format(Regex::new(rx).unwrap())
+            .query_zone(Zone::utc());
+        let lines = found(search, text);
+        assert_eq!(2, lines.len());
+        assert!(lines[0].ends_with("line 250"));
+        assert!(lines[1].ends_with("line 251"));
+    }
+}
+
+#[test]
+fn epoch_in_query_zone() {
+    let text = "1547262000 three o'clock UTC\n1547265600 four o'clock UTC\n";
+    let search = Search::new(date(2019, 1, 12, 4, 0, 0), date(2019, 1, 12, 5, 0, 0))
+        .format(Regex::new(r"^(?P<epoch>\d+)").unwrap())
+        .query_zone(zone("+01:00"));
+    assert_eq!(vec!["1547262000 three o'clock UTC"], found(search, text));
+}
+
+const SYSLOG: &str =
+    r"^(?P<month>\w{3}) +(?P<day>\d+) (?P<hour>\d\d):(?P<minute>\d\d):(?P<second>\d\d)";
+
+#[test]
+fn year_from_query() {
+    let text = r#"Dec 31 23:00:00 host one
+Dec 31 23:59:59 host two
+Jan  1 00:00:01 host three
+Jan  1 01:00:00 host four
+"#;
+    let search = Search::new(date(2019, 12, 31, 23, 30, 0), date(2020, 1, 1, 0, 30, 0))
+        .format(Regex::new(SYSLOG).unwrap());
+    assert_eq!(
+        vec!["Dec 31 23:59:59 host two", "Jan  1 00:00:01 host three"],
+        found(search, text)
+    );
+}
+
+#[test]
+fn year_from_modification_time() {
+    use std::fs::{self, File};
+    use std::time::{Duration as StdDuration, UNIX_EPOCH};
+    let path = std::env::temp_dir().join(format!("hun-syslog-{}.log", std::process::id()));
+    fs::write(
+        &path,
+        "Dec 31 23:00:00 host one\nJan  1 00:00:01 host two\nJan  1 01:00:00 host three\n",
+    )
+    .unwrap();
+    // the log was last written to at 2020-01-01 02:00:00 UTC
+    let modified = date(2020, 1, 1, 2, 0, 0).and_utc().timestamp() as u64;
+    File::options()
+        .write(true)
+        .open(&path)
+        .and_then(|f| f.set_modified(UNIX_EPOCH + StdDuration::from_secs(modified)))
+        .unwrap();
+    let search = |start, end| {
+        Search::new(start, end)
+            .format(Regex::new(SYSLOG).unwrap())
+            .log_zone(Zone::utc())
+            .query_zone(Zone::utc())
+            .run(Source::open(&path).unwrap())
+    };
+    let found = search(date(2019, 12, 31, 22, 0, 0), date(2020, 1, 1, 0, 30, 0));
+    // the same dates a year earlier are not in the log, whatever the query
+    let earlier = search(date(2018, 12, 31, 22, 0, 0), date(2019, 1, 1, 0, 30, 0));
+    fs::remove_file(&path).ok();
+    assert_eq!(
+        vec!["Dec 31 23:00:00 host one", "Jan  1 00:00:01 host two"],
+        found.unwrap().1
+    );
+    match earlier {
+        Err(Problem::LogAfter { .. }) => (),
+        Err(e) => panic!("wrong error: {:?}", e),
+        Ok(_) => panic!("this was supposed to throw an error"),
+    }
+}